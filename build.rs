@@ -0,0 +1,45 @@
+// Validates the built-in instructions' microcode when smiscasm is built (get_all_instructions validates them again at runtime).
+// The validation's modules are included from src, so the checks are the same ones.
+
+use std::fs;
+use std::path::PathBuf;
+
+#[allow(dead_code)]
+#[path = "src/util"]
+mod util {
+    pub mod exit;
+    pub mod remove_comments;
+}
+
+#[allow(dead_code)]
+#[path = "src/instruction"]
+mod instruction {
+    pub mod call_word;
+    pub mod micro_operation;
+    pub mod validation;
+}
+
+fn main() {
+    for path in ["instructions", "src/instruction/call_word.rs", "src/instruction/micro_operation.rs", "src/instruction/validation.rs"] {
+        println!("cargo:rerun-if-changed={}", path);
+    }
+
+    let mut paths = fs::read_dir("instructions").unwrap().filter_map(|x| x.ok()).map(|x| x.path()).filter(|x| x.extension().is_some_and(|y| y == "txt")).collect::<Vec<PathBuf>>();
+    paths.sort();
+
+    let mut problems_count = 0;
+
+    for path in paths.iter() {
+        let source = fs::read_to_string(path).unwrap();
+
+        for problem in instruction::validation::validate_instruction(&source) {
+            eprintln!("{}:{}: {}: {}", path.display(), problem.line + 1, problem.title, problem.message);
+            problems_count += 1;
+        }
+    }
+
+    if problems_count > 0 {
+        eprintln!("The instructions have {} microcode problems.", problems_count);
+        std::process::exit(1);
+    }
+}
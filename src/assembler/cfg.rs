@@ -0,0 +1,343 @@
+// The control flow graph of the tokenized code: which instruction can run after which.
+// Jump & call targets are resolved by following the values put into registers (adrp, add, sub & mov) along the control flow,
+// which is how labels are usually loaded (adrp x0, label@PAGE & add x0, label@PAGEOFF).
+
+use crate::assembler::lint::ENTRY_POINTS;
+use crate::assembler::tokenizer::{InstructionArgs, Line, TokenizerResult};
+use crate::config::MEMORY_PAGE_SIZE;
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::line_mapping::{CodeInterpretationMode, LineMap};
+
+/// Instructions that don't write to the register in their first operand.
+const NOT_WRITING: [&str; 8] = ["sb", "out", "jmp", "jmpz", "jmpr", "cal", "calr", "ret"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EdgeKind {
+    Next,       // Continues with the next instruction (also after a call returns)
+    Jump,       // Always jumps there
+    Branch,     // Jumps there if the condition is met
+    Call,       // Calls a routine there
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub to: usize,          // The node's index
+    pub kind: EdgeKind,
+}
+
+/// What comes after an instruction that continues, if it isn't another instruction in its section.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunsInto {
+    Data,           // Data (in the same or the next section)
+    SectionEnd,     // The end of the section (or the whole code)
+}
+
+/// An instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub line: usize,                    // In the line map (for errors & warnings)
+    pub address: u32,                   // In the image
+    pub section: String,
+    pub labels: Vec<String>,            // The labels pointing to it
+    pub code: String,                   // As written, without comments
+    pub successors: Vec<Edge>,
+    pub unresolved: bool,               // Jumps (or calls) somewhere that can't be followed
+    pub runs_into: Option<RunsInto>,
+    pub reachable: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControlFlowGraph {
+    pub nodes: Vec<Node>,
+    pub entries: Vec<usize>,            // Where the code is started (or called from outside)
+}
+
+/// Each code line's position in bytes (ignoring memory page alignment, like the value generation).
+fn positions(code: &TokenizerResult) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let mut bytes = 0;
+
+    for line in code.code.iter() {
+        positions.push(bytes);
+
+        bytes += match line {
+            Line::Instruction(..) => 4,
+            Line::RAW(data) => data.len(),
+        };
+    }
+
+    positions
+}
+
+/// The labels and the indexes of the code lines they point to.
+pub fn labels(code: &TokenizerResult) -> Vec<(String, usize)> {
+    let positions = positions(code);
+
+    code.global_constants.iter().filter(|x| x.get_is_function()).filter_map(|label| {
+        let value = label.get_value();
+        let (section_name, offset) = value.split_once(':')?;
+        let section = code.sections.iter().find(|x| x.name == section_name)?;
+        let position = section.start_pos_bytes_original + offset.parse::<usize>().ok()?;
+
+        Some((label.get_name(), positions.iter().position(|x| *x == position)?))
+    }).collect()
+}
+
+/// The target of a jump or call (as an address in the image), None if it can't be known.
+fn target(argument: Option<&InstructionArgs>, registers: &[Option<i64>; 32], address: u32, relative: bool) -> Option<i64> {
+    match argument? {
+        InstructionArgs::Immediate(value) if relative => Some(address as i64 + 4 + *value as i64),
+        InstructionArgs::Immediate(value) => Some(*value as i64),
+        InstructionArgs::Register(register) => registers[*register as usize],
+        InstructionArgs::Global(_) => None,
+    }
+}
+
+/// Whether an instruction continues with the next one and where it jumps to (with the registers' values before it).
+/// Jumps take the target as their last operand, the ones with two operands only jump if the condition is met.
+fn control_flow(name: &str, args: &[InstructionArgs], registers: &[Option<i64>; 32], address: u32) -> (bool, Option<(Option<i64>, EdgeKind)>) {
+    match name {
+        "jmp" | "jmpz" if args.len() > 1 || name == "jmpz" => (true, Some((target(args.last(), registers, address, false), EdgeKind::Branch))),
+        "jmp" => (false, Some((target(args.last(), registers, address, false), EdgeKind::Jump))),
+        "jmpr" => (false, Some((target(args.last(), registers, address, true), EdgeKind::Jump))),
+        "cal" => (true, Some((target(args.last(), registers, address, false), EdgeKind::Call))),
+        "calr" => (true, Some((target(args.last(), registers, address, true), EdgeKind::Call))),
+        "ret" | "hlt" => (false, None),
+        _ => (true, None),
+    }
+}
+
+/// The registers' values where two ways join: only the ones that are the same on both are known.
+fn merge(a: &[Option<i64>; 32], b: &[Option<i64>; 32]) -> [Option<i64>; 32] {
+    std::array::from_fn(|i| if a[i] == b[i] { a[i] } else { None })
+}
+
+/// Follow the values put into registers (None if unknown).
+fn track_registers(registers: &mut [Option<i64>; 32], name: &str, args: &[InstructionArgs]) {
+    let Some(InstructionArgs::Register(register)) = args.first() else { return; };
+    let register = *register as usize;
+
+    let operand = match args.get(1) {
+        Some(InstructionArgs::Immediate(value)) => Some(*value as i64),
+        Some(InstructionArgs::Register(other)) => registers[*other as usize],
+        _ => None,
+    };
+
+    registers[register] = match name {
+        "adrp" => operand.map(|x| x * MEMORY_PAGE_SIZE as i64),
+        "mov" => operand,
+        "add" => registers[register].zip(operand).map(|(x, y)| x + y),
+        "sub" => registers[register].zip(operand).map(|(x, y)| x - y),
+        name if NOT_WRITING.contains(&name) => registers[register],
+        _ => None,
+    };
+}
+
+impl ControlFlowGraph {
+    /// Build the graph of tokenized code (the line map is the tokenizer's, it only has lines for instructions).
+    /// Labels in `globals` can be called from other code.
+    pub fn build(code: &TokenizerResult, line_map: &LineMap, globals: &[String]) -> ControlFlowGraph {
+        let positions = positions(code);
+        let labels = labels(code);
+        let mut graph = ControlFlowGraph::default();
+        let mut line_of_node = Vec::new();      // The code line of each node
+
+        for (i, line) in code.code.iter().enumerate() {
+            let Line::Instruction(..) = line else { continue; };
+
+            // The last section starting before it (empty sections start at the same position as the next one)
+            let Some(section) = code.sections.iter().rev().find(|x| x.start_pos_bytes_original <= positions[i]) else { continue; };
+            let address = section.start_memory_page * MEMORY_PAGE_SIZE + section.start_offset + positions[i] - section.start_pos_bytes_original;
+            let line_number = line_of_node.len();
+            let written = line_map.lines.get(line_number).map(|x| x.contents.split('#').next().unwrap_or("").trim().to_string()).unwrap_or_default();
+
+            graph.nodes.push(Node {
+                line: line_number,
+                address: address as u32,
+                section: section.name.clone(),
+                labels: labels.iter().filter(|x| x.1 == i).map(|x| x.0.clone()).collect(),
+                code: written,
+                successors: Vec::new(),
+                unresolved: false,
+                runs_into: None,
+                reachable: false,
+            });
+            line_of_node.push(i);
+        }
+
+        let mut falls_through = vec![false; graph.nodes.len()];     // Whether each node continues with the next one
+
+        for (index, i) in line_of_node.iter().copied().enumerate() {
+            let Line::Instruction(name, args) = &code.code[i] else { continue; };
+            if !control_flow(name, args, &[None; 32], 0).0 { continue; }
+
+            // What comes next in memory
+            let next_section = code.sections.iter().rev().find(|x| x.start_pos_bytes_original <= positions.get(i + 1).copied().unwrap_or(usize::MAX));
+            let same_section = next_section.is_some_and(|x| x.name == graph.nodes[index].section);
+
+            match code.code.get(i + 1) {
+                Some(Line::Instruction(..)) if same_section => falls_through[index] = true,
+                Some(Line::Instruction(..)) if line_map.lines.get(index + 1).is_some_and(|x| matches!(x.attributes.mode, CodeInterpretationMode::Data)) => graph.nodes[index].runs_into = Some(RunsInto::Data),
+                Some(Line::RAW(_)) => graph.nodes[index].runs_into = Some(RunsInto::Data),
+                _ => graph.nodes[index].runs_into = Some(RunsInto::SectionEnd),
+            }
+        }
+
+        // The registers' values before each node. Nothing is known where code can be started from outside (anything the code before doesn't run into),
+        // where ways join only the values that are the same on all of them are kept.
+        let mut states: Vec<Option<[Option<i64>; 32]>> = vec![None; graph.nodes.len()];
+        let mut queue = (0..graph.nodes.len()).filter(|x| *x == 0 || !falls_through[x - 1]).collect::<Vec<usize>>();
+
+        for index in queue.iter() { states[*index] = Some([None; 32]); }
+
+        while let Some(index) = queue.pop() {
+            let Line::Instruction(name, args) = &code.code[line_of_node[index]] else { continue; };
+            let mut registers = states[index].unwrap_or([None; 32]);
+            let mut ways = Vec::new();
+
+            if let (_, Some((target, kind))) = control_flow(name, args, &registers, graph.nodes[index].address) {
+                // Routines are called from different places & change registers, so nothing is known in them or after calls
+                if let Some(to) = target.and_then(|x| graph.nodes.iter().position(|y| y.address as i64 == x)) {
+                    ways.push((to, if kind == EdgeKind::Call { [None; 32] } else { registers }));
+                }
+
+                if kind == EdgeKind::Call { registers = [None; 32]; }
+            }
+
+            track_registers(&mut registers, name, args);
+            if falls_through[index] { ways.push((index + 1, registers)); }
+
+            for (to, incoming) in ways {
+                let merged = states[to].map_or(incoming, |x| merge(&x, &incoming));
+
+                if states[to] != Some(merged) {
+                    states[to] = Some(merged);
+                    queue.push(to);
+                }
+            }
+        }
+
+        for (index, i) in line_of_node.iter().copied().enumerate() {
+            let Line::Instruction(name, args) = &code.code[i] else { continue; };
+
+            if let (_, Some((target, kind))) = control_flow(name, args, &states[index].unwrap_or([None; 32]), graph.nodes[index].address) {
+                match target.and_then(|x| graph.nodes.iter().position(|y| y.address as i64 == x)) {
+                    Some(to) => graph.nodes[index].successors.push(Edge { to, kind }),
+                    None => graph.nodes[index].unresolved = true,
+                }
+            }
+
+            if falls_through[index] { graph.nodes[index].successors.push(Edge { to: index + 1, kind: EdgeKind::Next }); }
+        }
+
+        // Programs start at main, libraries are called at their global labels (or the first instruction if there's neither).
+        // If anything jumps somewhere unknown, it could be any label.
+        let anything_unresolved = graph.nodes.iter().any(|x| x.unresolved);
+
+        graph.entries = graph.nodes.iter().enumerate().filter(|(_, node)| {
+            node.labels.iter().any(|x| ENTRY_POINTS.contains(&x.as_str()) || globals.contains(x)) || (anything_unresolved && !node.labels.is_empty())
+        }).map(|x| x.0).collect();
+
+        if graph.entries.is_empty() && !graph.nodes.is_empty() { graph.entries.push(0); }
+
+        let mut queue = graph.entries.clone();
+
+        while let Some(index) = queue.pop() {
+            if graph.nodes[index].reachable { continue; }
+
+            graph.nodes[index].reachable = true;
+            queue.extend(graph.nodes[index].successors.iter().map(|x| x.to));
+        }
+
+        graph
+    }
+}
+
+/// Report unreachable instructions and code that runs into data or off the end of its section.
+pub fn check_control_flow(graph: &ControlFlowGraph, line_map: &mut LineMap) {
+    let mut i = 0;
+
+    while i < graph.nodes.len() {
+        let node = &graph.nodes[i];
+
+        if node.reachable {
+            let end = line_map.last_token(node.line as u32);
+
+            match node.runs_into {
+                Some(RunsInto::Data) => {
+                    let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, node.line as u32, 0, end, "Fall-Through Into Data".to_string(), "The code continues after this instruction, but what comes next is data, which would be run as code.".to_string());
+                    diagnostic.notes.push("End the code with hlt, ret or a jump.".to_string());
+                }
+
+                Some(RunsInto::SectionEnd) => {
+                    let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, node.line as u32, 0, end, "Missing Terminator".to_string(), format!("The code continues after this instruction, but section \"{}\" ends here.", node.section));
+                    diagnostic.notes.push("End the code with hlt, ret or a jump, whatever comes next in memory would be run otherwise.".to_string());
+                }
+
+                None => {}
+            }
+
+            i += 1;
+            continue;
+        }
+
+        // Report a run of unreachable instructions once
+        let start = i;
+        while i < graph.nodes.len() && !graph.nodes[i].reachable && graph.nodes[i].section == node.section && (i == start || graph.nodes[i].labels.is_empty() || graph.nodes[i - 1].successors.iter().any(|x| x.to == i)) { i += 1; }
+
+        let count = i - start;
+        let message = match node.labels.first() {
+            Some(label) => format!("Nothing jumps to \"{}\" or runs into it, so {} instruction{} can't be reached.", label, count, if count == 1 { "" } else { "s" }),
+            None => format!("The code before doesn't continue here and nothing jumps here, so {} instruction{} can't be reached.", count, if count == 1 { "" } else { "s" }),
+        };
+
+        let end = line_map.last_token(node.line as u32);
+        let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, node.line as u32, 0, end, "Unreachable Code".to_string(), message);
+        diagnostic.notes.push("Remove it, jump to it or export its label with .global if it's called from other code.".to_string());
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assembler::{assemble, AssembleOptions};
+    use crate::assembler::cfg::EdgeKind;
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::util::file_system::MemoryFileSystem;
+
+    const CODE: &str = ".section \"CODE\"\n.mode text\n_main:\n    adrp x17, _loop@PAGE\n    add x17, _loop@PAGEOFF\n    adrp x18, _end@PAGE\n    add x18, _end@PAGEOFF\n_loop:\n    sub x1, 1\n    jmpz x1, x18\n    jmp x17\n    out x1\n_end:\n    hlt\n_dead:\n    hlt\n_runs_off:\n    mov x0, 1\n.section \"DATA\"\n.mode data\nmsg:\n    .ascii \"Hi\"\n.global _runs_off\n";
+
+    #[tokio::test]
+    async fn test_build() {
+        let assembled = assemble(CODE.to_string(), get_all_instructions(), &MemoryFileSystem::new(), AssembleOptions::new("main.s".to_string())).await.unwrap();
+        let graph = assembled.cfg;
+
+        assert_eq!(graph.nodes.len(), 11);
+        assert_eq!(graph.entries, vec![0, 10]);
+        assert_eq!(graph.nodes[4].labels, vec!["_loop".to_string()]);
+        assert_eq!(graph.nodes[5].code, "jmpz x1, x18");
+        assert_eq!(graph.nodes[5].successors.iter().map(|x| (x.to, x.kind)).collect::<Vec<(usize, EdgeKind)>>(), vec![(8, EdgeKind::Branch), (6, EdgeKind::Next)]);
+        assert_eq!(graph.nodes[6].successors.iter().map(|x| (x.to, x.kind)).collect::<Vec<(usize, EdgeKind)>>(), vec![(4, EdgeKind::Jump)]);
+        assert_eq!(graph.nodes.iter().map(|x| x.reachable).collect::<Vec<bool>>(), vec![true, true, true, true, true, true, true, false, true, false, true]);
+    }
+
+    #[tokio::test]
+    async fn test_check_control_flow() {
+        let assembled = assemble(CODE.to_string(), get_all_instructions(), &MemoryFileSystem::new(), AssembleOptions::new("main.s".to_string())).await.unwrap();
+        let warnings = assembled.diagnostics.iter().filter(|x| x.title != "Unused Label").map(|x| (x.title.as_str(), x.primary.as_ref().unwrap().line)).collect::<Vec<(&str, u32)>>();
+
+        assert_eq!(warnings, vec![("Unreachable Code", 12), ("Unreachable Code", 16), ("Fall-Through Into Data", 18)]);
+    }
+
+    #[tokio::test]
+    async fn test_build_joins() {
+        // x0 is _b when the branch is taken and _a otherwise, so it's unknown at _join.
+        let code = ".section \"CODE\"\n.mode text\n_main:\n    adrp x0, _b@PAGE\n    add x0, _b@PAGEOFF\n    jmp x1, _join@PAGEOFF\n    adrp x0, _a@PAGE\n    add x0, _a@PAGEOFF\n_join:\n    cal x0\n    hlt\n_a:\n    ret\n_b:\n    ret\n";
+        let assembled = assemble(code.to_string(), get_all_instructions(), &MemoryFileSystem::new(), AssembleOptions::new("main.s".to_string())).await.unwrap();
+        let graph = assembled.cfg;
+
+        assert!(graph.nodes[5].successors.iter().all(|x| x.kind != EdgeKind::Call) && graph.nodes[5].unresolved);
+        assert!(graph.nodes.iter().all(|x| x.reachable));
+        assert!(assembled.diagnostics.iter().all(|x| x.title != "Unreachable Code"));
+    }
+}
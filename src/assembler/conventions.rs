@@ -0,0 +1,190 @@
+// Checks the register conventions from the README on the tokenized code (only with --check-registers):
+// callee-saved registers (x8...x15 & sp) have to be restored before a routine returns, reserved registers (x24...x30) aren't written at all.
+// What instructions do is read from their micro operations, so it works for other instruction sets, too.
+
+use crate::assembler::cfg::labels;
+use crate::assembler::tokenizer::{InstructionArgs, Line, TokenizerResult};
+use crate::instruction::call_word::{PM_FLAG, ZF_FLAG};
+use crate::instruction::instruction::Instruction;
+use crate::instruction::micro_operation::active_micro_operations;
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::line_mapping::LineMap;
+
+const CALLEE_SAVED: std::ops::RangeInclusive<u8> = 8..=15;
+const RESERVED: std::ops::RangeInclusive<u8> = 24..=30;
+const SP: u8 = 31;
+
+/// What an instruction does, as far as the conventions are concerned.
+#[derive(Debug, Default, PartialEq)]
+struct Effects {
+    writes: Vec<usize>,     // The register operands (by index) that are written
+    loads: Vec<usize>,      // The register operands that are written with a value from memory (restored)
+    halts: bool,            // The program counter isn't incremented, so nothing comes after it
+    returns: bool,          // Pops the return address from the stack into the program counter
+}
+
+fn effects(instruction: &Instruction) -> Effects {
+    let mut effects = Effects::default();
+    let mut increments_pc = false;
+    let mut increments_sp = false;
+    let mut sets_pc = false;
+
+    for (stage, control_word) in instruction.stages.iter() {
+        let names = active_micro_operations(*control_word).iter().map(|x| x.name.as_str()).collect::<Vec<&str>>();
+        let from_memory = names.iter().any(|x| x.starts_with("MEM_") && x.ends_with("_OUT"));
+
+        // Stages that only run with flags set aren't always run
+        if stage & (ZF_FLAG | PM_FLAG) == 0 {
+            increments_pc |= names.contains(&"PC_INC");
+        }
+
+        increments_sp |= names.contains(&"SP_INC_DW");
+        sets_pc |= names.contains(&"PC_IN");
+
+        for (operand, micro_operation) in ["CAL_REG_A_IN", "CAL_REG_B_IN"].iter().enumerate() {
+            if !names.contains(micro_operation) || effects.writes.contains(&operand) { continue; }
+
+            effects.writes.push(operand);
+            if from_memory { effects.loads.push(operand); }
+        }
+    }
+
+    effects.halts = !increments_pc;
+    effects.returns = increments_sp && sets_pc;
+
+    effects
+}
+
+/// The instruction the assembler will use for a line (like the last step chooses it).
+fn find_instruction<'a>(instructions: &'a [Instruction], name: &str, args: &[InstructionArgs]) -> Option<&'a Instruction> {
+    let format = args.iter().map(|x| matches!(x, InstructionArgs::Register(_))).collect::<Vec<bool>>();
+
+    instructions.iter().find(|x| x.name == name && x.format.iter().map(|y| y.is_register()).collect::<Vec<bool>>() == format)
+}
+
+/// A routine's state: where callee-saved registers were last changed and how far sp was moved.
+struct Routine {
+    clobbered: Vec<(u8, usize)>,        // Register & the line it was changed in
+    sp_offset: i32,
+    sp_changed: Option<usize>,          // The last line sp was changed in
+    sp_unknown: bool,                   // sp was set to a value that can't be followed
+}
+
+impl Routine {
+    fn new() -> Routine {
+        Routine { clobbered: Vec::new(), sp_offset: 0, sp_changed: None, sp_unknown: false }
+    }
+}
+
+/// Follow each routine (from a label to its ret) and report callee-saved registers that aren't restored & writes to reserved registers.
+/// The code is followed linearly: a routine ends at the first ret (or an instruction that halts) after it starts.
+pub fn check_register_conventions(code: &TokenizerResult, instructions: &[Instruction], line_map: &mut LineMap) {
+    let labels = labels(code).iter().map(|x| x.1).collect::<Vec<usize>>();
+    let mut routine: Option<Routine> = None;
+    let mut line_number = 0;        // The line map only has lines for instructions
+
+    for (i, line) in code.code.iter().enumerate() {
+        if labels.contains(&i) && routine.is_none() { routine = Some(Routine::new()); }
+
+        let Line::Instruction(name, args) = line else {
+            routine = None;
+            continue;
+        };
+
+        let current_line = line_number;
+        line_number += 1;
+
+        let Some(instruction) = find_instruction(instructions, name, args) else { continue; };
+        let effects = effects(instruction);
+
+        for operand in effects.writes.iter() {
+            let Some(InstructionArgs::Register(register)) = args.get(*operand) else { continue; };
+            let register = *register;
+
+            if RESERVED.contains(&register) {
+                let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, current_line as u32, 0, line_map.last_token(current_line as u32), "Reserved Register Written".to_string(), format!("x{} is reserved (x24...x30), it shouldn't be written.", register));
+                diagnostic.notes.push("Use a caller-saved register (x16...x23) instead.".to_string());
+            }
+
+            let Some(routine) = routine.as_mut() else { continue; };
+
+            if register == SP {
+                match (name.as_str(), args.get(1)) {
+                    ("add", Some(InstructionArgs::Immediate(value))) => routine.sp_offset += value,
+                    ("sub", Some(InstructionArgs::Immediate(value))) => routine.sp_offset -= value,
+                    _ => routine.sp_unknown = true,
+                }
+
+                routine.sp_changed = Some(current_line);
+            } else if CALLEE_SAVED.contains(&register) {
+                routine.clobbered.retain(|x| x.0 != register);
+
+                if !effects.loads.contains(operand) { routine.clobbered.push((register, current_line)); }
+            }
+        }
+
+        if effects.halts {
+            routine = None;
+            continue;
+        }
+
+        if !effects.returns { continue; }
+        let Some(finished) = routine.take() else { continue; };
+
+        for (register, changed_line) in finished.clobbered.iter() {
+            let ret_span = line_map.span(current_line as u32, Some(0), None);
+
+            let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, *changed_line as u32, 0, line_map.last_token(*changed_line as u32), "Callee-Saved Register Not Restored".to_string(), format!("x{} is callee-saved, but it's changed here and not restored before the routine returns.", register));
+            diagnostic.secondary.push((ret_span, "The routine returns here".to_string()));
+            diagnostic.notes.push("Store it on the stack before changing it and load it back before ret, or use a caller-saved register (x16...x23).".to_string());
+        }
+
+        if let Some(changed_line) = finished.sp_changed && (finished.sp_unknown || finished.sp_offset != 0) {
+            let ret_span = line_map.span(current_line as u32, Some(0), None);
+
+            let message = match finished.sp_unknown {
+                true => "sp is callee-saved, but it's set here and can't be followed to the routine's return.".to_string(),
+                false => format!("sp is callee-saved, but it's {} bytes off when the routine returns.", finished.sp_offset),
+            };
+
+            let diagnostic = line_map.report_tokens(ErrorNotificationKind::Warning, changed_line as u32, 0, line_map.last_token(changed_line as u32), "Callee-Saved Register Not Restored".to_string(), message);
+            diagnostic.secondary.push((ret_span, "The routine returns here".to_string()));
+            diagnostic.notes.push("Move sp back by as much as it was moved (add & sub with immediate values) before ret.".to_string());
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assembler::{assemble, AssembleOptions};
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::util::file_system::MemoryFileSystem;
+
+    async fn warnings(code: &str) -> Vec<(String, u32)> {
+        let mut options = AssembleOptions::new("main.s".to_string());
+        options.check_registers = true;
+
+        let assembled = assemble(code.to_string(), get_all_instructions(), &MemoryFileSystem::new(), options).await.unwrap();
+        assembled.diagnostics.iter().map(|x| (x.title.clone(), x.primary.as_ref().unwrap().line)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_check_register_conventions() {
+        let code = ".global _clobbers\n.global _restores\n.global _leaks\n.section \"CODE\"\n.mode text\n_main:\n    mov x24, x0\n    hlt\n_clobbers:\n    mov x8, x0\n    add x16, 1\n    ret\n_restores:\n    sub sp, 4\n    sb x8, x31\n    mov x8, x0\n    lb x8, x31\n    add sp, 4\n    ret\n_leaks:\n    sub sp, 8\n    ret\n";
+
+        assert_eq!(warnings(code).await, vec![
+            ("Reserved Register Written".to_string(), 7),
+            ("Callee-Saved Register Not Restored".to_string(), 10),
+            ("Callee-Saved Register Not Restored".to_string(), 21),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_check_registers_is_optional() {
+        let code = ".section \"CODE\"\n.mode text\n_main:\n    mov x8, x0\n    ret\n";
+        let assembled = assemble(code.to_string(), get_all_instructions(), &MemoryFileSystem::new(), AssembleOptions::new("main.s".to_string())).await.unwrap();
+
+        assert!(assembled.diagnostics.is_empty());
+    }
+}
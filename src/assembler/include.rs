@@ -1,22 +1,22 @@
-use std::error;
 use std::fs::*;
-use std::io::Write;
 use std::string::ToString;
-use colorize::AnsiColor;
-use reqwest::get;
 use url::Url;
-use crate::expand_path;
 use crate::config::*;
-use crate::util::code_error::{display_code_error, ErrorNotificationKind};
-use crate::util::exit::{exit, ExitCode};
+use crate::library::cache::{download, record_download, take_from_shared};
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::{Diagnostic, Span};
+use crate::util::file_system::FileSystem;
 use crate::util::line_mapping::{ LineMap, LineInfo };
 
 
 /// Recursively goes throw all "!include"s and includes them recursively.
-pub async fn perform_inclusions(code: String, file_name: String) -> (String, LineMap) {
+/// Files are read from `files`, the shared library cache & downloads are only used if `fetch_libraries` is set.
+/// Downloads are reported to `progress` (if there is one) while they happen, nothing is printed.
+pub async fn perform_inclusions(code: String, file_name: String, files: &dyn FileSystem, fetch_libraries: bool, progress: Option<fn(&str)>) -> (String, LineMap) {
     let mut result: Vec<String> = vec![];
     let mut line_map = LineMap::new();
 
+    let source_file_name = file_name.clone();
     line_map.set_current_file_name(file_name);
 
 
@@ -39,59 +39,60 @@ pub async fn perform_inclusions(code: String, file_name: String) -> (String, Lin
 
 
         // Look if the file exists.
-        if let Some(file) = expand_path(inclusion_argument.as_str()) {
-            if let Some(file_contents) = read_to_string(file).ok() {
-                let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument)).await;
-                result.push(file_contents.0.clone());
+        if let Some(file_contents) = files.read_to_string(inclusion_argument.as_str()) {
+            let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument, files, fetch_libraries, progress)).await;
+            add_included(&mut result, &mut line_map, file_contents);
 
-                // Update line mapping
-                for i in 0..file_contents.1.lines.len() {
-                    line_map.add_line(file_contents.1.lines[i].clone());
-                }
-
-                current_line_number += 1;
+            current_line_number += 1;
 
-                continue;
-            }
+            continue;
         }
 
         // Couldn't get file
         // Retry with URL
         // But check if it's already been downloaded first.
-        let file_name = PUBLIC_LIBS_DIR.to_string() + inclusion_argument.split('/').last().unwrap();
+        let file_name = PUBLIC_LIBS_DIR.to_string() + library_file_name(&inclusion_argument);
 
         // Check if it exists already
-        if let Some(file) = expand_path(file_name.as_str()) {
-            if let Some(file_contents) = read_to_string(file).ok() {
-                let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument)).await;
-                result.push(file_contents.0.clone());
+        if let Some(file_contents) = files.read_to_string(file_name.as_str()) {
+            let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument, files, fetch_libraries, progress)).await;
+            add_included(&mut result, &mut line_map, file_contents);
 
-                // Update line mapping
-                for i in 0..file_contents.1.lines.len() {
-                    line_map.add_line(file_contents.1.lines[i].clone());
-                }
+            current_line_number += 1;
 
-                current_line_number += 1;
+            continue;
+        }
 
-                continue;
-            }
+        // Not downloaded for this project, but maybe for another one.
+        if fetch_libraries && let Some(file_contents) = take_from_shared(library_file_name(&inclusion_argument)) {
+            let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument, files, fetch_libraries, progress)).await;
+            add_included(&mut result, &mut line_map, file_contents);
+
+            current_line_number += 1;
+
+            continue;
         }
 
         // Doesn't exist
         // Make sure pub-libs exists
-        if create_dir_all(PUBLIC_LIBS_DIR).is_err() {
-            exit("Couldn't create public libraries directory to store public libraries in.".to_string(), ExitCode::ReadWriteError);
+        if fetch_libraries && create_dir_all(PUBLIC_LIBS_DIR).is_err() {
+            report_include_error(&mut line_map, source_file_name.as_str(), line, current_line_number, inclusion_argument.len(), "Library Download Failed".to_string(), "Couldn't create public libraries directory to store public libraries in.".to_string());
+            current_line_number += 1;
+            continue;
         }
 
         // Try to download the URL
-        if let Some(include_source_code) = attempt_download(inclusion_argument.clone(), file_name.clone(), inclusion_argument.clone()).await {
-            let file_contents = Box::pin(perform_inclusions(include_source_code, inclusion_argument)).await;
-            result.push(file_contents.0.clone());
+        let downloaded = if fetch_libraries { attempt_download(inclusion_argument.clone(), file_name.clone(), inclusion_argument.clone(), progress).await } else { Ok(None) };
 
-            // Update line mapping
-            for i in 0..file_contents.1.lines.len() {
-                line_map.add_line(file_contents.1.lines[i].clone());
-            }
+        if let Err(message) = downloaded {
+            report_include_error(&mut line_map, source_file_name.as_str(), line, current_line_number, inclusion_argument.len(), "Library Download Failed".to_string(), message);
+            current_line_number += 1;
+            continue;
+        }
+
+        if let Ok(Some(include_source_code)) = downloaded {
+            let file_contents = Box::pin(perform_inclusions(include_source_code, inclusion_argument, files, fetch_libraries, progress)).await;
+            add_included(&mut result, &mut line_map, file_contents);
 
             current_line_number += 1;
 
@@ -109,14 +110,19 @@ pub async fn perform_inclusions(code: String, file_name: String) -> (String, Lin
             let file_name = PUBLIC_LIBS_DIR.to_string().clone() + package_info.2;
 
             // Look if it has been downloaded already.
-            if let Some(file_contents) = read_to_string(file_name.clone()).ok() {
-                let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument)).await;
-                result.push(file_contents.0.clone());
+            if let Some(file_contents) = files.read_to_string(file_name.as_str()) {
+                let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument, files, fetch_libraries, progress)).await;
+                add_included(&mut result, &mut line_map, file_contents);
+
+                current_line_number += 1;
+
+                continue;
+            }
 
-                // Update line mapping
-                for i in 0..file_contents.1.lines.len() {
-                    line_map.add_line(file_contents.1.lines[i].clone());
-                }
+            // Maybe another project downloaded it already.
+            if fetch_libraries && let Some(file_contents) = take_from_shared(package_info.2) {
+                let file_contents = Box::pin(perform_inclusions(file_contents, inclusion_argument, files, fetch_libraries, progress)).await;
+                add_included(&mut result, &mut line_map, file_contents);
 
                 current_line_number += 1;
 
@@ -124,14 +130,17 @@ pub async fn perform_inclusions(code: String, file_name: String) -> (String, Lin
             }
 
             // Doesn't exist -> Download it
-            if let Some(include_source_code) = attempt_download(url, file_name, inclusion_argument.clone()).await {
-                let file_contents = Box::pin(perform_inclusions(include_source_code, inclusion_argument)).await;
-                result.push(file_contents.0.clone());
+            let downloaded = if fetch_libraries { attempt_download(url, file_name, inclusion_argument.clone(), progress).await } else { Ok(None) };
 
-                // Update line mapping
-                for i in 0..file_contents.1.lines.len() {
-                    line_map.add_line(file_contents.1.lines[i].clone());
-                }
+            if let Err(message) = downloaded {
+                report_include_error(&mut line_map, source_file_name.as_str(), line, current_line_number, inclusion_argument.len(), "Library Download Failed".to_string(), message);
+                current_line_number += 1;
+                continue;
+            }
+
+            if let Ok(Some(include_source_code)) = downloaded {
+                let file_contents = Box::pin(perform_inclusions(include_source_code, inclusion_argument, files, fetch_libraries, progress)).await;
+                add_included(&mut result, &mut line_map, file_contents);
 
                 current_line_number += 1;
 
@@ -141,70 +150,76 @@ pub async fn perform_inclusions(code: String, file_name: String) -> (String, Lin
 
 
         // No include option worked -> throw error
-        let mut code: Vec<String> = vec![];
+        report_include_error(&mut line_map, source_file_name.as_str(), line, current_line_number, inclusion_argument.len(), "Library not found".to_string(), "There was no file found with that name, no URL with that name could be reached and there's no such well-known public library.".to_string());
+        current_line_number += 1;
+    }
 
-        // Create newlines except for the last (current) line
+    let code = result.join("\n");
 
-        for _ in 0..current_line_number{
-            code.push(String::new());
-        }
+    (code, line_map)
+}
 
-        code.push(line.to_string());
+/// The name a library is stored under in pub-libs (the last part of an !include's argument or URL).
+fn library_file_name(inclusion_argument: &str) -> &str {
+    inclusion_argument.rsplit('/').next().unwrap_or(inclusion_argument)
+}
 
-        let column = 9; // The length of the !include statement
-        display_code_error(ErrorNotificationKind::Error, current_line_number as i32, Some(column), Some(inclusion_argument.len() as u32), "Library not found".to_string(), "There was no file found with that name, no URL with that name could be reached and there's no such well-known public library.".to_string(), code, "".to_string());
-        line_map.errors_count += 1;
-        line_map.stop_after_step = true;
-    }
+/// Where an !include's argument is on disk without downloading anything (the file itself, then pub-libs), None if it isn't there.
+pub fn local_include_path(inclusion_argument: &str, files: &dyn FileSystem) -> Option<String> {
+    let mut candidates = vec![inclusion_argument.to_string(), PUBLIC_LIBS_DIR.to_string() + library_file_name(inclusion_argument)];
 
-    let code = result.join("\n");
+    if let Some(package_info) = KNOWN_PUBLIC_LIBS.iter().find(|&x| x.0 == inclusion_argument) {
+        candidates.push(PUBLIC_LIBS_DIR.to_string() + package_info.2);
+    }
 
+    candidates.into_iter().find(|x| files.read(x.as_str()).is_some())
+}
 
-    line_map.exit_if_needed();
+/// Add an included file's code & lines (as well as its errors & warnings).
+fn add_included(result: &mut Vec<String>, line_map: &mut LineMap, included: (String, LineMap)) {
+    result.push(included.0);
 
-    (code, line_map)
-}
+    for line in included.1.lines.iter() {
+        line_map.add_line(line.clone());
+    }
 
-fn print_failed(){
-    let msg = "failed".red();
-    println!("{}", msg);
+    line_map.errors_count += included.1.errors_count;
+    line_map.warnings_count += included.1.warnings_count;
+    line_map.diagnostics.extend(included.1.diagnostics);
+    line_map.stop_after_step |= included.1.stop_after_step;
 }
 
-async fn download(url: String, name: String) -> Result<(), Box<dyn error::Error>> {
-    let response = get(url).await?;
-    let content = response.bytes().await?;
+/// Underline the argument of an !include statement that couldn't be included.
+fn report_include_error(line_map: &mut LineMap, file_name: &str, line: &str, line_number: u32, length: usize, title: String, message: String) {
+    let column = 9; // The length of the !include statement
+    let span = Span { file_name: file_name.to_string(), line: line_number, indent: 0, column: Some(column), length: Some(length as u32), code: line.to_string() };
 
-    let mut downloaded_file = File::create(name)?;
-    downloaded_file.write_all(&content)?;
+    line_map.push(Diagnostic::new(ErrorNotificationKind::Error, title, message, Some(span)));
 
-    Ok(())
+    // Everything the file would have defined is missing, which would only cause more errors.
+    line_map.stop_after_step = true;
 }
 
-async fn attempt_download(line: String, file_name: String, download_name: String) -> Option<String> {
-    if Url::parse(line.as_str()).is_ok() {
-        let msg = format!("Trying to download: {} as {} ... ", download_name, file_name);
-        print!("{}", msg);
-        if download(line.clone(), file_name.to_string()).await.is_ok(){
-            if let Some(d_file) = expand_path(file_name.as_str()) {
-                if let Some(file_contents) = read_to_string(d_file).ok() {
-                    let msg = "ok".green();
-                    println!("{}", msg);
-
-                    return Some(file_contents);
-                } else {
-                    print_failed();
-                    exit("Download of dependency failed, are you in the same dir as the file to assemble?".to_string(), ExitCode::Other);
-                }
-            } else {
-                print_failed();
-                exit("Download of dependency failed, are you in the same dir as the file to assemble? Are you connected to the internet?".to_string(), ExitCode::Other);
-            }
-        }
+/// Download a library if the line is a URL. Err contains what went wrong.
+async fn attempt_download(line: String, file_name: String, download_name: String, progress: Option<fn(&str)>) -> Result<Option<String>, String> {
+    if Url::parse(line.as_str()).is_err() { return Ok(None); }
+
+    if let Some(progress) = progress { progress(&format!("Downloading {} as {}", download_name, file_name)); }
+
+    let Ok(contents) = download(line.clone()).await else {
+        return Err(format!("Download of dependency {} failed.\nAre you connected to the internet?", line));
+    };
 
-        // Download failed
-        print_failed();
-        exit(format!("Download of dependency {} failed.\nAre you in the same dir as the file to assemble?\nAre you connected to the internet?", line).to_string(), ExitCode::Other);
+    // Remember where it came from (this also stores it in pub-libs)
+    if record_download(library_file_name(&file_name), line.as_str(), &contents).is_err() {
+        return Err(format!("Couldn't store dependency in {}.", PUBLIC_LIBS_DIR));
     }
 
-    None
-}
\ No newline at end of file
+    let Ok(file_contents) = String::from_utf8(contents) else {
+        return Err(format!("The downloaded dependency {} isn't text.", line));
+    };
+
+    if let Some(progress) = progress { progress(&format!("Downloaded {}", download_name)); }
+
+    Ok(Some(file_contents))
+}
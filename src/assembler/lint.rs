@@ -0,0 +1,105 @@
+// Checks that don't change what's assembled, but point out likely mistakes (on the split code, before values are generated).
+
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::line_mapping::LineMap;
+
+/// Labels that are used without being referenced (where programs start).
+pub const ENTRY_POINTS: [&str; 2] = ["main", "_main"];
+
+/// The names declared with .global (whether they're defined or not).
+pub fn global_names(code: &[Vec<String>]) -> Vec<String> {
+    code.iter().filter(|x| x.len() == 3 && x[0] == "." && x[1] == "global").map(|x| x[2].clone()).collect()
+}
+
+/// Report labels & constants that are never referenced and globals that are never defined.
+/// Globals are exported, so they don't have to be referenced.
+pub fn lint_unused(code: &[Vec<String>], line_map: &mut LineMap) {
+    let mut definitions: Vec<(String, usize, bool)> = Vec::new();     // Name, line, whether it's a label
+    let mut globals: Vec<(String, usize)> = Vec::new();
+    let mut references: Vec<&String> = Vec::new();
+
+    for (line_number, line) in code.iter().enumerate() {
+        if line.is_empty() { continue; }
+
+        // Labels
+        if line.len() == 2 && line[1] == ":" {
+            definitions.push((line[0].clone(), line_number, true));
+            continue;
+        }
+
+        if line[0] == "." && line.len() > 1 {
+            match line[1].as_str() {
+                "global" if line.len() == 3 => globals.push((line[2].clone(), line_number)),
+
+                // Only strings & numbers
+                "section" | "mode" | "ascii" | "stc" | "incbin" | "global" => {}
+
+                // Constants (their values can refer to others)
+                name => {
+                    definitions.push((name.to_string(), line_number, false));
+                    references.extend(line[2..].iter());
+                }
+            }
+
+            continue;
+        }
+
+        // Instructions: everything but the name could be a reference.
+        references.extend(line[1..].iter());
+    }
+
+    for (name, line_number, is_label) in definitions.iter() {
+        if references.contains(&name) || globals.iter().any(|x| x.0 == *name) { continue; }
+        if *is_label && ENTRY_POINTS.contains(&name.as_str()) { continue; }
+
+        let diagnostic = match is_label {
+            true => line_map.report(ErrorNotificationKind::Warning, *line_number as u32, Some(0), "Unused Label".to_string(), format!("The label \"{}\" is never referenced.", name)),
+            false => line_map.report(ErrorNotificationKind::Warning, *line_number as u32, Some(1), "Unused Constant".to_string(), format!("The constant \"{}\" is never used.", name)),
+        };
+
+        diagnostic.notes.push("Remove it, or export it with .global if other code should use it.".to_string());
+    }
+
+    for (name, line_number) in globals.iter() {
+        if definitions.iter().any(|x| x.0 == *name) { continue; }
+
+        line_map.report(ErrorNotificationKind::Warning, *line_number as u32, Some(2), "Undefined Global".to_string(), format!("\"{}\" is declared global, but there's no label or constant with that name.", name));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::lint::lint_unused;
+    use crate::util::line_mapping::LineMap;
+
+    fn tokens(lines: Vec<Vec<&str>>) -> Vec<Vec<String>> {
+        lines.iter().map(|x| x.iter().map(|x| x.to_string()).collect()).collect()
+    }
+
+    #[test]
+    fn test_lint_unused() {
+        let code = tokens(vec![
+            vec![".", "section", "\"", "CODE", "\""],
+            vec![".", "msg_len", "13"],
+            vec![".", "unused_value", "[", "msg_len", "+", "1", "]"],
+            vec![".", "exported", "1"],
+            vec![".", "global", "exported"],
+            vec![".", "global", "typo"],
+            vec!["main", ":"],
+            vec!["adrp", "x0", ",", "msg", "@", "PAGE"],
+            vec!["jmp", "main"],
+            vec!["dead", ":"],
+            vec!["hlt"],
+            vec!["msg", ":"],
+            vec![".", "ascii", "\"", "dead", "\""],
+        ]);
+
+        let mut line_map = LineMap::test_map();
+        lint_unused(&code, &mut line_map);
+
+        let reported = line_map.diagnostics.iter().map(|x| (x.title.as_str(), x.primary.as_ref().unwrap().line)).collect::<Vec<(&str, u32)>>();
+        assert_eq!(reported, vec![("Unused Constant", 2), ("Unused Label", 9), ("Undefined Global", 5)]);
+        assert_eq!(line_map.warnings_count, 3);
+    }
+}
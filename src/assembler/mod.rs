@@ -1,9 +1,12 @@
 pub mod assembler;
-mod include;
+pub mod include;
+pub mod cfg;
+pub mod conventions;
+pub mod lint;
 pub mod valuegen;
 mod valuerepl;
 pub mod splitter;
-mod zstep;
+pub mod zstep;
 pub mod preprocesser;
 pub mod tokenizer;
 pub mod order_sections;
\ No newline at end of file
@@ -52,11 +52,7 @@ pub async fn preprocess(code: String, input_line_map: LineMap) -> (Vec<String>,
     lines = lines_with_content;
 
 
-    output_line_map.warnings_count = input_line_map.warnings_count;
-    output_line_map.errors_count = input_line_map.errors_count;
-
-
-    output_line_map.exit_if_needed();
+    output_line_map.carry_over(&input_line_map);
 
     (lines, output_line_map)
 }
@@ -1,4 +1,5 @@
-use crate::util::code_error::{display_code_error, ErrorNotificationKind};
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::{Diagnostic, Span};
 use crate::util::line_mapping::LineMap;
 
 /// Split lines into tokens
@@ -101,19 +102,10 @@ pub fn split(input: Vec<String>, mut input_line_map: LineMap) -> (Vec<Vec<String
                 if in_string_literal {
                     // Check the literal is valid
                     if current_token.is_empty() {
-                        let mut code = vec![];
-
                         let real_line_number = input_line_map.lines[line_number].line_number;
+                        let span = Span { file_name: file_name.clone(), line: real_line_number, indent: input_line_map.lines[line_number].indent, column: Some((current_token_start - 1) as u32), length: Some((current_token.len() + 2) as u32), code: line.clone() };
 
-                        for _ in 0..real_line_number {
-                            code.push("".to_string());
-                        }
-
-                        code.push(line.clone());
-
-                        display_code_error(ErrorNotificationKind::Error, real_line_number as i32, Some((current_token_start - 1) as u32), Some((current_token.len() + 2) as u32), "Empty String Literal".to_string(), "Empty string literals are not allowed, but an empty string literal was found here.".to_string(), code, file_name.clone());
-                        input_line_map.errors_count += 1;
-                        output_line_map.stop_after_step = true;
+                        input_line_map.push(Diagnostic::new(ErrorNotificationKind::Error, "Empty String Literal".to_string(), "Empty string literals are not allowed, but an empty string literal was found here.".to_string(), Some(span)));
                     }
                     // Add the current token & the ''' or the '"'.
                     current_line_token_map.push((current_token_start as u32, current_token.len() as u32));
@@ -153,18 +145,10 @@ pub fn split(input: Vec<String>, mut input_line_map: LineMap) -> (Vec<Vec<String
 
         if in_string_literal {
             let real_line_number = input_line_map.lines[line_number].line_number;
+            let span = Span { file_name: file_name.clone(), line: real_line_number, indent: input_line_map.lines[line_number].indent, column: Some((current_token_start - 1) as u32), length: Some((current_char_count - current_token_start + 2) as u32), code: line.clone() };
 
-            let mut code: Vec<String> = vec![];
-
-            for _ in 0..real_line_number {
-                code.push("".to_string());
-            }
-
-            code.push(line.clone());
-
-
-            display_code_error(ErrorNotificationKind::Error, real_line_number as i32, Some((current_token_start - 1) as u32), Some((current_char_count - current_token_start + 2) as u32), "Unterminated String Literal".to_string(), "String literals always need to be terminated, but this one wasn't closed.\nAdd the missing \".".to_string(), code, file_name);
-            input_line_map.errors_count += 1;
+            // The rest of the line is part of the literal now, which would only cause confusing errors later on.
+            input_line_map.push(Diagnostic::new(ErrorNotificationKind::Error, "Unterminated String Literal".to_string(), "String literals always need to be terminated, but this one wasn't closed.\nAdd the missing \".".to_string(), Some(span)));
             output_line_map.stop_after_step = true;
         }
 
@@ -191,11 +175,7 @@ pub fn split(input: Vec<String>, mut input_line_map: LineMap) -> (Vec<Vec<String
     }
 
 
-    output_line_map.warnings_count = input_line_map.warnings_count;
-    output_line_map.errors_count = input_line_map.errors_count;
-
-
-    output_line_map.exit_if_needed();
+    output_line_map.carry_over(&input_line_map);
 
     (all_tokens, output_line_map)
 }
@@ -1,15 +1,15 @@
 use std::process::Command;
-use crate::config::*;
 use crate::util::replacement::Replacement;
 use crate::assembler::valuerepl::{LineKind, ValueReplResult};
 use crate::assembler::tokenizer::InstructionArgs::{Global, Immediate, Register};
 use crate::assembler::valuegen::Section;
 use crate::util::code_error::ErrorNotificationKind;
+use crate::util::file_system::FileSystem;
 use crate::util::line_mapping::LineMap;
 
 // Yet another tokenizer
-/// Tokenizes ValueReplResult into YATokenizerResult
-pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap) -> (TokenizerResult, LineMap) {
+/// Tokenizes ValueReplResult into YATokenizerResult (reading .incbin files from `files`)
+pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap, files: &dyn FileSystem) -> (TokenizerResult, LineMap) {
     let mut result = TokenizerResult {
         code: vec![],
         global_constants: vec![],
@@ -37,6 +37,23 @@ pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap) -> (Tokenize
                 continue;
             }
 
+            LineKind::INCBIN => {
+                let file_name = code[0].clone();
+                let offset = code[1].parse::<usize>().unwrap_or(0);
+                let length = code[2].parse::<usize>().unwrap_or(0);
+
+                // The file has been read before (to count its bytes), but it might have changed since.
+                let contents = files.read(file_name.as_str()).filter(|x| offset.checked_add(length).is_some_and(|end| end <= x.len()));
+
+                if let Some(contents) = contents {
+                    result.code.push(Line::RAW(contents[offset..offset + length].to_vec()));
+                } else {
+                    input_line_map.report(ErrorNotificationKind::Error, line_number as u32, Some(0), "File Not Found".to_string(), format!("Couldn't read {} bytes at byte {} from \"{}\" anymore.", length, offset, file_name));
+                }
+
+                continue;
+            }
+
             LineKind::STC => {
                 let text = code[0].clone();
 
@@ -77,19 +94,9 @@ pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap) -> (Tokenize
                 for token in code[1..].iter() {
                     current_token_index += 1;
                     // If it can be passed immediately, it's just an immediate value.
+                    // Whether it fits is checked once the instruction (and therefore the operand's kind) is known.
                     if let Some(value) = token.parse::<i32>().ok() {
-                        if value.abs() > MEMORY_PAGE_SIZE as i32 {
-                            input_line_map.print_notification(ErrorNotificationKind::Error, line_number as u32, Some(current_token_index), "Value Outside Of Immediate Range".to_string(), format!("Value of {} decimal is not in the range of an immediate value ({} decimal to {} decimal).", value, -(MEMORY_PAGE_SIZE as isize), MEMORY_PAGE_SIZE - 1));
-                        }
-                        // Correct the format
-                        // Choose last 12 bits
-                        let mut immediate_value: u16 = (value & 0x00_00_0F_FF) as u16;
-
-                        if value.is_negative() {
-                            immediate_value = immediate_value | 0x00_00_10_00;
-                        }
-
-                        args.push(Immediate(immediate_value));
+                        args.push(Immediate(value));
                         continue;
                     }
 
@@ -101,7 +108,7 @@ pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap) -> (Tokenize
                     if first_char == 'x'{
                         if let Some(value) = token_except_first_char.parse::<i32>().ok(){
                             if value > 31 || value < 0 {
-                                input_line_map.print_notification(ErrorNotificationKind::Error, line_number as u32, Some(current_token_index - 1), "Unknown Register".to_string(), format!("No such register: \"{}\". Known registers include x0...x31 and sp (which links to x31).", token));
+                                input_line_map.report(ErrorNotificationKind::Error, line_number as u32, Some(current_token_index - 1), "Unknown Register".to_string(), format!("No such register: \"{}\". Known registers include x0...x31 and sp (which links to x31).", token));
                                 continue 'line_loop;
                             }
 
@@ -123,11 +130,7 @@ pub fn tokenize(from: ValueReplResult, mut input_line_map: LineMap) -> (Tokenize
     }
 
 
-    output_line_map.errors_count = input_line_map.errors_count;
-    output_line_map.warnings_count = input_line_map.warnings_count;
-
-
-    output_line_map.exit_if_needed();
+    output_line_map.carry_over(&input_line_map);
 
     (result, output_line_map)
 }
@@ -148,7 +151,7 @@ pub enum Line{
 #[derive(Debug, Clone, PartialEq)]
 pub enum InstructionArgs{
     Register(u8),                                   // Only least significant 6 bits
-    Immediate(u16),                                 // Least significant 11 are value, the 12th one from the right is the sign, the 13th one is "shift by eleven bits"
+    Immediate(i32),                                 // The value as written, range-checked & encoded according to the instruction
     Global(String),                                 // The name of the global constant
 }
 
@@ -160,6 +163,7 @@ mod tests {
     use crate::assembler::valuegen::Section;
     use crate::util::line_mapping::LineMap;
     use crate::util::replacement::Replacement;
+    use crate::util::file_system::DiskFileSystem;
 
     #[test]
     fn test_tokenize(){
@@ -205,7 +209,7 @@ mod tests {
 
         let line_map = LineMap::test_map();
 
-        let result = tokenize(input, line_map);
+        let result = tokenize(input, line_map, &DiskFileSystem);
         let output_code = result.0.code.clone();
 
         assert_eq!(output_code.len(), expected_output_code.len());
@@ -1,13 +1,15 @@
 use std::i32;
 use convert_case::{Case, Casing};
-use crate::config::DEFAULT_MODE;
+use crate::config::{DEFAULT_MODE, MEMORY_PAGE_SIZE};
 use crate::util::code_error::ErrorNotificationKind;
+use crate::util::file_system::FileSystem;
 use crate::util::replacement::Replacement;
 use crate::util::math::resolve_string;
 use crate::util::line_mapping::{CodeInterpretationMode, LineMap};
 
 /// Find global constant declarations and labels (function definitions) in code and separate them.
-pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenResult, LineMap){
+/// Files for .incbin are read from `files`.
+pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap, files: &dyn FileSystem) -> (ValueGenResult, LineMap){
     let mut result = ValueGenResult{ constants: vec![], sections: vec![], code: vec![], line_mapping: vec![] };
     let mut output_line_map = LineMap::new();
 
@@ -20,8 +22,9 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
     let mut global_constants_names: Vec<String> = Vec::new();
 
-    // What kind of info the assembler expects
+    // What kind of info the assembler expects (and where it's been set if it has been)
     let mut mode: CodeInterpretationMode = DEFAULT_MODE;
+    let mut mode_line_number: Option<usize> = None;
 
     // Go through the code line by line and resolve all the ones starting with a '.'.
     for i in code.iter().enumerate(){
@@ -36,19 +39,19 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
             let command = line.iter().nth(1);
 
             if command.is_none() {
-                input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Unused assembler command".to_string(), "Expected assembler command or constant name after \".\".".to_string());
+                input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Unused assembler command".to_string(), "Expected assembler command or constant name after \".\".".to_string());
                 continue;
             }
 
             match command.cloned().unwrap().clone().as_str() {
                 "section" => {
                     if line.len() != 5 {
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The compiler command \"section\" requires a string as an argument, but no string was found.".to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The compiler command \"section\" requires a string as an argument, but no string was found.".to_string());
                         continue;
                     }
 
                     if line[2] != "\"" || line[4] != "\""{
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The compiler command \"section\" requires a string as an argument, but no string was found.".to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The compiler command \"section\" requires a string as an argument, but no string was found.".to_string());
                         continue;
                     }
 
@@ -56,7 +59,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
                     // Look if the name is uppercase as it should be
                     if name.to_ascii_uppercase() != name {
-                        input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(3), "Naming Convention Not Met".to_string(), format!("Section names should be uppercase like \"{}\".", name.to_ascii_uppercase()));
+                        input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(3), "Naming Convention Not Met".to_string(), format!("Section names should be uppercase like \"{}\".", name.to_ascii_uppercase()));
                     }
 
                     //let section: (String, u32) = (name, bytes_count as u32);
@@ -76,7 +79,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
                 "ascii" | "stc" => {
                     if line.len() != 5{
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a string as an argument, but no string was found.", command.unwrap() /*ascii or stc*/).to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a string as an argument, but no string was found.", command.unwrap() /*ascii or stc*/).to_string());
                         continue;
                     }
 
@@ -85,7 +88,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                     let second_quote = line.iter().nth(4).unwrap();
 
                     if first_quote != "\"" || second_quote != "\"" {
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a string as an argument, but no string was found.", command.unwrap() /*ascii or stc*/).to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a string as an argument, but no string was found.", command.unwrap() /*ascii or stc*/).to_string());
                         continue;
                     }
 
@@ -111,14 +114,108 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                     // Look if the mode in use is qualified for data
                     if !(matches!(mode, CodeInterpretationMode::None) || matches!(mode, CodeInterpretationMode::Data)) {
                         // Probably in text section, throw a warning
-                        input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(1), "Data In Non-Data Mode".to_string(), "This is not written in a data-accepting mode. Add \".mode data\" before this too remove this warning.".parse().unwrap());
+                        let mode_span = mode_line_number.map(|x| input_line_map.span(x as u32, Some(2), None));
+                        let diagnostic = input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(1), "Data In Non-Data Mode".to_string(), "This is not written in a data-accepting mode. Add \".mode data\" before this too remove this warning.".to_string());
+                        diagnostic.secondary.extend(mode_span.map(|x| (x, "The mode is set here".to_string())));
+                    }
+                }
+
+                "incbin" => {
+                    // .incbin "file" [, offset [, length]]
+                    if line.len() < 5 || line[2] != "\"" || line[4] != "\"" {
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and incbin are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The compiler command \"incbin\" requires a file name as a string as its first argument, but no string was found.".to_string());
+                        continue;
+                    }
+
+                    let file_name = line[3].clone();
+
+                    // Split the optional offset and length at the commas and calculate them.
+                    let mut numeric_args: Vec<Vec<String>> = vec![];
+
+                    for token in line[5..].iter() {
+                        if token == "," {
+                            numeric_args.push(vec![]);
+                            continue;
+                        }
+
+                        if let Some(arg) = numeric_args.last_mut() {
+                            arg.push(token.clone());
+                        }
+                    }
+
+                    if (line.len() > 5 && numeric_args.is_empty()) || numeric_args.len() > 2 || numeric_args.iter().any(|x| x.is_empty()) {
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 5, (line.len() - 1) as u32, "Compiler command formatting error".to_string(), "The compiler command \"incbin\" takes at most an offset and a length (separated by commas) after the file name.".to_string());
+                        continue;
+                    }
+
+                    let mut numeric_values: Vec<usize> = vec![];
+
+                    for arg in numeric_args.iter() {
+                        let constants = [result.constants.clone(), vec![Replacement::new("$".to_string(), bytes_count.to_string(), true)]].concat();
+                        let value = resolve_string(arg.join(" ").replace("0x ", "0x").replace("0o ", "0o").replace("0b ", "0b"), constants);
+
+                        if let Ok(value) = value.parse::<usize>() {
+                            numeric_values.push(value);
+                        } else {
+                            break;
+                        }
+                    }
+
+                    if numeric_values.len() != numeric_args.len() {
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 5, (line.len() - 1) as u32, "Compiler command formatting error".to_string(), "The offset and length of \"incbin\" must be non-negative integer values.".to_string());
+                        continue;
+                    }
+
+                    // Read the file now to know how many bytes it'll take up.
+                    let contents = files.read(file_name.as_str());
+
+                    if contents.is_none() {
+                        input_line_map.report(ErrorNotificationKind::Error, line_number as u32, Some(3), "File Not Found".to_string(), format!("Couldn't read the file \"{}\" to include its bytes.", file_name));
+                        continue;
+                    }
+
+                    let contents = contents.unwrap();
+                    let offset = numeric_values.first().cloned().unwrap_or(0);
+                    let length = numeric_values.get(1).cloned().unwrap_or(contents.len().saturating_sub(offset));
+
+                    if offset.checked_add(length).is_none_or(|end| end > contents.len()) {
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 5, (line.len() - 1) as u32, "Out Of File Bounds".to_string(), format!("Can't include {} bytes starting at byte {} from \"{}\" as the file is only {} bytes long.", length, offset, file_name, contents.len()));
+                        continue;
+                    }
+
+                    // Increment the byte counter
+                    bytes_count += length;
+
+                    if bytes_count - current_section_start > MEMORY_PAGE_SIZE {
+                        input_line_map.report(ErrorNotificationKind::Error, line_number as u32, Some(3), "Section Too Long".to_string(), format!("Including {} bytes from \"{}\" makes this section {}B long, but no section should be longer than a memory page ({}B).", length, file_name, bytes_count - current_section_start, MEMORY_PAGE_SIZE));
+                    }
+
+                    // The bytes are read again by the tokenizer, only keep what's needed to find them.
+                    result.code.push(vec![first_token.unwrap().to_string(), command.unwrap().to_string(), file_name.clone(), offset.to_string(), length.to_string()]);
+
+                    // Add the correct line mapping with the file name (and its quotes) as one token
+                    let mut line = input_line_map.lines[line_number].clone();
+                    let start = line.token_info[2].0;
+                    let end = line.token_info[4].0 + line.token_info[4].1;
+                    line.token_info.truncate(2);
+                    line.token_info.push((start, end - start));
+                    output_line_map.add_line(line);
+
+                    let line_number_in_result = result.code.len() - 1;
+                    result.line_mapping.push((line_number_in_result, line_number));
+
+                    // Look if the mode in use is qualified for data
+                    if !(matches!(mode, CodeInterpretationMode::None) || matches!(mode, CodeInterpretationMode::Data)) {
+                        let mode_span = mode_line_number.map(|x| input_line_map.span(x as u32, Some(2), None));
+                        let diagnostic = input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(1), "Data In Non-Data Mode".to_string(), "This is not written in a data-accepting mode. Add \".mode data\" before this too remove this warning.".to_string());
+                        diagnostic.secondary.extend(mode_span.map(|x| (x, "The mode is set here".to_string())));
                     }
                 }
 
                 "mode" => {
                     // Change what kind of data is expected
                     if line.len() != 3{
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The mode command requires a mode (such as text, data or none) as an argument, but this was not satisfied.".to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), "The mode command requires a mode (such as text, data or none) as an argument, but this was not satisfied.".to_string());
 
                         // Set the mode to None to not generate unnecessary complaints
                         mode = CodeInterpretationMode::None;
@@ -134,7 +231,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                         "none" => { mode = CodeInterpretationMode::None; }
                         "text" | "code" => { mode = CodeInterpretationMode::Text; }
                         _ => {
-                            input_line_map.print_notification(ErrorNotificationKind::Error, line_number as u32, Some(2), "No Such Mode".to_string(), format!("There is no such mode: {}. Available modes include: data, text & none.", mode_text));
+                            input_line_map.report(ErrorNotificationKind::Error, line_number as u32, Some(2), "No Such Mode".to_string(), format!("There is no such mode: {}. Available modes include: data, text & none.", mode_text));
 
                             // Set the mode to None to not generate unnecessary complaints
                             mode = CodeInterpretationMode::None;
@@ -146,15 +243,16 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
                     // Set the mode to preserve the information for later stages
                     output_line_map.set_mode(mode);
+                    mode_line_number = Some(line_number);
 
                     if mode_text.to_lowercase().as_str() != mode_text{
-                        input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(2), "Mode Naming Convention Not Followed".to_string(), format!("Mode names should be lowercase, but \"{}\" doesn't follow this.", mode_text));
+                        input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(2), "Mode Naming Convention Not Followed".to_string(), format!("Mode names should be lowercase, but \"{}\" doesn't follow this.", mode_text));
                     }
                 }
 
                 "global" => {
                     if line.len() != 3{
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and global are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a variable name but this was found instead.", command.unwrap() /*ascii or stc*/).to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and global are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The compiler command \"{}\" requires a variable name but this was found instead.", command.unwrap() /*ascii or stc*/).to_string());
                         continue;
                     }
                     let global_variable_name = line[2].clone();
@@ -169,7 +267,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                     let mut value: Option<String> = None;
 
                     if first_value_token.is_none(){
-                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 0 /* start at the .*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("Can't create constant named \"{}\" without a value.", command.cloned().unwrap() /*ascii or stc*/).to_string());
+                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 0 /* start at the .*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("Can't create constant named \"{}\" without a value.", command.cloned().unwrap() /*ascii or stc*/).to_string());
                         continue;
                     }
 
@@ -177,16 +275,16 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                     if first_value_token.unwrap() == "[" {
                         if line.iter().len() == 7{
                             if line.iter().nth(6).unwrap() != "]"{
-                                input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be read.", command.cloned().unwrap() /*ascii or stc*/).to_string());
+                                input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be read.", command.cloned().unwrap() /*ascii or stc*/).to_string());
                                 continue;
                             }
 
                             let math_op = vec![line.iter().nth(3).unwrap().to_string(), line.iter().nth(4).unwrap().to_string(), line.iter().nth(5).unwrap().to_string()].join(" ");
-                            let constants = vec![result.constants.clone(), vec![Replacement::new("$".to_string(), bytes_count.to_string(), true)]].concat();
+                            let constants = [result.constants.clone(), vec![Replacement::new("$".to_string(), bytes_count.to_string(), true)]].concat();
                             let result_str = resolve_string(math_op, constants);
 
                             if result_str == "" {
-                                input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be calculated.", command.cloned().unwrap() /*ascii or stc*/).to_string());
+                                input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and section are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be calculated.", command.cloned().unwrap() /*ascii or stc*/).to_string());
                                 continue;
                             }
 
@@ -201,7 +299,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                             if let Some(value_i32) = value_token.parse::<i32>().ok(){
                                 value = Some(value_i32.to_string());
                             } else {
-                                input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a base-10 integer value.", command.cloned().unwrap()).to_string());
+                                input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a base-10 integer value.", command.cloned().unwrap()).to_string());
                                 continue;
                             }
                         } else if line.iter().len() == 4 {
@@ -213,7 +311,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                                     if let Some(value_i32) = i32::from_str_radix(number, 16).ok(){
                                         value = Some(value_i32.to_string());
                                     } else {
-                                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a hexadecimal integer value.", command.cloned().unwrap()).to_string());
+                                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a hexadecimal integer value.", command.cloned().unwrap()).to_string());
                                         continue;
                                     }
                                 }
@@ -222,7 +320,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                                     if let Some(value_i32) = i32::from_str_radix(number, 8).ok(){
                                         value = Some(value_i32.to_string());
                                     } else {
-                                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as an octal integer value.", command.cloned().unwrap()).to_string());
+                                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as an octal integer value.", command.cloned().unwrap()).to_string());
                                         continue;
                                     }
                                 }
@@ -231,18 +329,18 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
                                     if let Some(value_i32) = i32::from_str_radix(number, 2).ok(){
                                         value = Some(value_i32.to_string());
                                     } else {
-                                        input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a binary integer value.", command.cloned().unwrap()).to_string());
+                                        input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded as a binary integer value.", command.cloned().unwrap()).to_string());
                                         continue;
                                     }
                                 }
 
                                 _ => {
-                                    input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded.", command.cloned().unwrap()).to_string());
+                                    input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded.", command.cloned().unwrap()).to_string());
                                     continue;
                                 }
                             }
                         } else {
-                            input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded. Format not understood.", command.cloned().unwrap()).to_string());
+                            input_line_map.report_tokens(ErrorNotificationKind::Error, line_number as u32, 2 /* both . and name are fine*/, (line.len() - 1) as u32 /* all remaining tokens*/, "Compiler command formatting error".to_string(), format!("The value of the constant named \"{}\" couldn't be decoded. Format not understood.", command.cloned().unwrap()).to_string());
                             continue;
                         }
                     }
@@ -250,7 +348,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
                     // Check if snake case was used
                     if command.unwrap().clone().to_string() != command.unwrap().clone().to_string().to_case(Case::Snake){
-                        input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(1), "Naming Convention Not Met".to_string(), format!("Constant names should be snake case like \"{}\".", command.unwrap().clone().to_string().to_case(Case::Snake)));
+                        input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(1), "Naming Convention Not Met".to_string(), format!("Constant names should be snake case like \"{}\".", command.unwrap().clone().to_string().to_case(Case::Snake)));
                     }
 
 
@@ -279,7 +377,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
             if matches!(mode.clone(), CodeInterpretationMode::Text){
                 // Expected _ prefix. Also, remove the character from the test.
                 if test_label_name.chars().nth(0) != Some('_') {
-                    input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Naming Convention Not Met".to_string(), "The label misses the _ prefix or the mode is not set correctly.".to_string());
+                    input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Naming Convention Not Met".to_string(), "The label misses the _ prefix or the mode is not set correctly.".to_string());
                 }
                 test_label_name.remove(0);
             }
@@ -287,7 +385,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
 
             // Check whether snake case was used as expected
             if test_label_name != test_label_name.to_case(Case::Snake) {
-                input_line_map.print_notification(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Naming Convention Not Met".to_string(), format!("The label should be snake case like \"(_){}\"", test_label_name.to_case(Case::Snake)));
+                input_line_map.report(ErrorNotificationKind::Warning, line_number as u32, Some(0), "Naming Convention Not Met".to_string(), format!("The label should be snake case like \"(_){}\"", test_label_name.to_case(Case::Snake)));
             }
 
 
@@ -314,7 +412,9 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
         if !matches!(mode.clone(), CodeInterpretationMode::Text) && !matches!(mode.clone(), CodeInterpretationMode::None) {
             // Instructions in data mode!
             // Be a snitch, report 'em.
-            input_line_map.print_notification_multiple_faulty_tokens(ErrorNotificationKind::Warning, line_number as u32, 0, (line.len() - 1) as u32, "Code In Inappropriate Section".to_string(), "All code should be in text sections, but is in a data section.".to_string());
+            let mode_span = mode_line_number.map(|x| input_line_map.span(x as u32, Some(2), None));
+            let diagnostic = input_line_map.report_tokens(ErrorNotificationKind::Warning, line_number as u32, 0, (line.len() - 1) as u32, "Code In Inappropriate Section".to_string(), "All code should be in text sections, but is in a data section.".to_string());
+            diagnostic.secondary.extend(mode_span.map(|x| (x, "The mode is set here".to_string())));
         }
 
         let line_number_in_result = result.code.len() - 1;
@@ -334,11 +434,7 @@ pub fn gen_values(code: Vec<Vec<String>>, input_line_map: LineMap) -> (ValueGenR
     }
 
 
-    output_line_map.errors_count = input_line_map.errors_count;
-    output_line_map.warnings_count = input_line_map.warnings_count;
-
-
-    output_line_map.exit_if_needed();
+    output_line_map.carry_over(&input_line_map);
 
     (result, output_line_map)
 }
@@ -363,6 +459,7 @@ mod tests{
     use crate::assembler::valuegen::{gen_values, Section};
     use crate::util::line_mapping::{LineInfo, LineMap};
     use crate::util::replacement::Replacement;
+    use crate::util::file_system::DiskFileSystem;
 
     #[test]
     fn test_gen_values(){
@@ -405,7 +502,7 @@ mod tests{
 
         let line_map = LineMap::test_map();
 
-        let result = gen_values(data, line_map);
+        let result = gen_values(data, line_map, &DiskFileSystem);
 
         assert_eq!(result.0.constants.len(), expected_constants.len());
         assert_eq!(result.0.code.len(), expected_code.len());
@@ -433,4 +530,66 @@ mod tests{
         }
 
     }
+
+    #[test]
+    fn test_gen_values_incbin(){
+        // test_code/incbin.bin is 8 bytes long
+        let data = vec![
+            vec![".".to_string(), "section".to_string(), "\"".to_string(), "DATA".to_string(), "\"".to_string()],
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/incbin.bin".to_string(), "\"".to_string(), ",".to_string(), "1".to_string(), ",".to_string(), "4".to_string()],
+            vec!["after_part".to_string(), ":".to_string()],
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/incbin.bin".to_string(), "\"".to_string(), ",".to_string(), "0x".to_string(), "2".to_string()],
+            vec!["after_rest".to_string(), ":".to_string()],
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/incbin.bin".to_string(), "\"".to_string()],
+            vec!["after_all".to_string(), ":".to_string()],
+        ];
+
+        let expected_constants = vec![
+            Replacement::new("after_part".to_string(), "DATA:4".to_string(), true),
+            Replacement::new("after_rest".to_string(), "DATA:10".to_string(), true),
+            Replacement::new("after_all".to_string(), "DATA:18".to_string(), true),
+        ];
+
+        let expected_code = vec![
+            vec![".", "incbin", "test_code/incbin.bin", "1", "4"],
+            vec![".", "incbin", "test_code/incbin.bin", "2", "6"],
+            vec![".", "incbin", "test_code/incbin.bin", "0", "8"],
+        ];
+
+        let result = gen_values(data, LineMap::test_map(), &DiskFileSystem);
+
+        assert_eq!(result.1.errors_count, 0);
+        assert_eq!(result.0.constants, expected_constants);
+        assert_eq!(result.0.code, expected_code);
+
+        // Missing files and reading past the end are errors
+        let data = vec![
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/does_not_exist.bin".to_string(), "\"".to_string()],
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/incbin.bin".to_string(), "\"".to_string(), ",".to_string(), "4".to_string(), ",".to_string(), "5".to_string()],
+            vec![".".to_string(), "incbin".to_string(), "\"".to_string(), "test_code/incbin.bin".to_string(), "\"".to_string(), ",".to_string(), "18446744073709551615".to_string(), ",".to_string(), "2".to_string()],
+        ];
+
+        let result = gen_values(data, LineMap::test_map(), &DiskFileSystem);
+
+        assert_eq!(result.1.errors_count, 3);
+        assert!(result.0.code.is_empty());
+    }
+
+    #[test]
+    fn test_gen_values_mode_warnings(){
+        let data = vec![
+            vec![".".to_string(), "mode".to_string(), "data".to_string()],
+            vec!["add".to_string(), "x0".to_string(), ",".to_string(), "1".to_string()],
+        ];
+
+        let result = gen_values(data, LineMap::test_map(), &DiskFileSystem);
+        let diagnostics = result.1.diagnostics;
+
+        // The warning points to where the mode has been set
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].title, "Code In Inappropriate Section");
+        assert_eq!(diagnostics[0].primary.clone().unwrap().line, 1);
+        assert_eq!(diagnostics[0].secondary[0].0.line, 0);
+        assert_eq!(diagnostics[0].secondary[0].1, "The mode is set here");
+    }
 }
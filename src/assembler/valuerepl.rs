@@ -1,5 +1,6 @@
 use crate::assembler::valuegen::{Section, ValueGenResult};
-use crate::util::code_error::{display_code_error, ErrorNotificationKind};
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::Diagnostic;
 use crate::util::line_mapping::LineMap;
 use crate::util::math::resolve_argument;
 use crate::util::replacement::Replacement;
@@ -30,8 +31,6 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
 
         let first_token = first_token.unwrap();
 
-        let source_file_name = input_line_mapping.lines[line_number].clone().source_file_name;
-
         if first_token == "." {
             // At this stage, the only thing it could be is data.
             let second_token = line[1].clone();
@@ -41,7 +40,7 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
                     let text = line[2].clone();
 
                     if !text.is_ascii() {
-                        input_line_mapping.print_notification(ErrorNotificationKind::Error, line_number as u32, Some(2), "Illegal Character in ASCII".to_string(), "There are illegal characters in this string. Those characters can't be passed as ASCII".to_string());
+                        input_line_mapping.report(ErrorNotificationKind::Error, line_number as u32, Some(2), "Illegal Character in ASCII".to_string(), "There are illegal characters in this string. Those characters can't be passed as ASCII".to_string());
                         input_line_mapping.stop_after_step = true;
                         continue;
                     }
@@ -66,7 +65,7 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
                     let text = line[2].clone();
 
                     if !text.is_ascii() {
-                        input_line_mapping.print_notification(ErrorNotificationKind::Error, line_number as u32, Some(2), "Illegal Character in STC".to_string(), "There are illegal characters in this string.\nThose characters can't be passed as STC.\n(Note: The string is only tested for non-ASCII characters.)".to_string());
+                        input_line_mapping.report(ErrorNotificationKind::Error, line_number as u32, Some(2), "Illegal Character in STC".to_string(), "There are illegal characters in this string.\nThose characters can't be passed as STC.\n(Note: The string is only tested for non-ASCII characters.)".to_string());
                         input_line_mapping.stop_after_step = true;
                         continue;
                     }
@@ -90,8 +89,22 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
                     continue;
                 }
 
+                "incbin" => {
+                    // File name, offset & length have been checked by valuegen already.
+                    result.code.push((line[2..].to_vec(), LineKind::INCBIN));
+
+                    // The file name (with its quotes) is the only token worth highlighting.
+                    let mut input_line_info = input_line_mapping.lines[line_number].clone();
+                    let file_name_info = input_line_info.token_info[2];
+                    input_line_info.token_info = vec![file_name_info];
+
+                    output_line_mapping.add_line(input_line_info);
+
+                    continue;
+                }
+
                 _ => {
-                    input_line_mapping.print_notification(ErrorNotificationKind::Error, line_number as u32, None, "Unknown Assembler Instruction".to_string(), format!("No such assembler instruction: \"{}\".\nNote: This is most likely an internal error with data likely caused by \"valuegen\".", second_token));
+                    input_line_mapping.report(ErrorNotificationKind::Error, line_number as u32, None, "Unknown Assembler Instruction".to_string(), format!("No such assembler instruction: \"{}\".\nNote: This is most likely an internal error with data likely caused by \"valuegen\".", second_token));
                     continue;
                 }
             }
@@ -165,19 +178,12 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
             }
 
             // None of the above decoding attempts succeeded, throw an error
-            let mut code: Vec<String> = Vec::with_capacity(line.len());
-            let real_line_number = input_line_mapping.lines[line_number].line_number;
-
-            for _ in 0..real_line_number {
-                code.push(String::new());
-            }
-
-            code.push(input_line_mapping.lines[line_number].contents.clone());
+            let mut span = input_line_mapping.span(line_number as u32, None, None);
+            span.column = Some(start_pos);
+            span.length = Some(in_between_tokens_length);
 
-
-            display_code_error(ErrorNotificationKind::Error, real_line_number as i32, Some(start_pos), Some(in_between_tokens_length), "Argument Decoding Error".to_string(), "Can't decode this argument. Check whether all variables exist if applicable.".to_string(), code, source_file_name.clone());
+            input_line_mapping.push(Diagnostic::new(ErrorNotificationKind::Error, "Argument Decoding Error".to_string(), "Can't decode this argument. Check whether all variables exist if applicable.".to_string(), Some(span)));
             input_line_mapping.stop_after_step = true;
-            input_line_mapping.errors_count += 1;
         }
 
         // Add the instruction back to the top of the chain
@@ -193,13 +199,9 @@ pub fn replace_values_in_code(code: ValueGenResult, mut input_line_mapping: Line
     }
 
 
-    output_line_mapping.warnings_count = input_line_mapping.warnings_count;
-    output_line_mapping.errors_count = input_line_mapping.errors_count;
+    output_line_mapping.carry_over(&input_line_mapping);
     output_line_mapping.stop_after_step = input_line_mapping.stop_after_step;
 
-
-    output_line_mapping.exit_if_needed();
-
     (result, output_line_mapping)
 }
 
@@ -215,7 +217,8 @@ pub struct ValueReplResult{
 pub enum LineKind {
     Code(bool),     // bool: immediate value?
     ASCII,
-    STC
+    STC,
+    INCBIN,     // File name, offset & length
 }
 
 
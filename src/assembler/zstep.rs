@@ -1,8 +1,10 @@
-use colorize::AnsiColor;
 use crate::config::*;
 use crate::assembler::tokenizer::{InstructionArgs, Line, TokenizerResult};
 use crate::instruction::instruction::*;
+use crate::instruction::encoding::ENCODING;
+use crate::instruction::operand::OperandKind;
 use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::Diagnostic;
 use crate::util::line_mapping::LineMap;
 // This name is really bad, ik
 // I just want RustRover to sort all of them in a reasonable order.
@@ -10,9 +12,29 @@ use crate::util::line_mapping::LineMap;
 // The Z represents 'last'
 
 /// Turns YATokenizerResult into the final binary
-pub fn perform_last_step(input: TokenizerResult, instructions: Vec<Instruction>, mut input_line_map: LineMap) -> (Vec<u8>, LineMap) {
+pub fn perform_last_step(input: TokenizerResult, instructions: Vec<Instruction>, mut input_line_map: LineMap) -> (Vec<u8>, Vec<PlacedSection>, LineMap) {
     let mut result: Vec<u8> = Vec::new();
+    let mut placed_sections: Vec<PlacedSection> = Vec::new(); // Where the sections ended up, without the zero fill
     let mut next_page_start = MEMORY_PAGE_SIZE;
+
+    // Nothing to place (e.g. an empty file), but code without a section can't be placed either
+    if input.sections.is_empty() {
+        if !input.code.is_empty() {
+            let message = "Code has to be in a section, but there's no .section before it.".to_string();
+
+            let diagnostic = match input_line_map.lines.is_empty() {
+                true => input_line_map.push(Diagnostic::new(ErrorNotificationKind::Error, "Code Outside Section".to_string(), message, None)),
+                false => input_line_map.report(ErrorNotificationKind::Error, 0, Some(0), "Code Outside Section".to_string(), message),
+            };
+            diagnostic.notes.push("Start the code with .section \"<name>\".".to_string());
+        }
+
+        let mut output_line_map = LineMap::new();
+        output_line_map.carry_over(&input_line_map);
+
+        return (result, placed_sections, output_line_map);
+    }
+
     let mut section_starts_in_lines = input.sections.iter().map(|x| x.clone().start_pos_bytes_original as u32).collect::<Vec<u32>>(); // The lines' (in the input code) indexes that belong to a new section. The first element is always the next section.
     let mut current_section_name = input.sections.iter().nth(0).unwrap().clone().name.clone();
     let mut actual_bytes_written: u32 = 0; // The bytes written that were not written for memory page aligning
@@ -30,10 +52,12 @@ pub fn perform_last_step(input: TokenizerResult, instructions: Vec<Instruction>,
                 // Look if the section was too long
                 if result.len() > next_page_start {
                     // Too long, throw error
-                    println!("{}", "Error: Section Too Long".red().bold());
-                    println!("{}", format!("Section \"{}\" is too long.\nNo section should be longer than a memory page ({}B), but this section is {}B long.", current_section_name, MEMORY_PAGE_SIZE, result.len()).red());
-                    input_line_map.errors_count += 1;
+                    let message = format!("Section \"{}\" is too long.\nNo section should be longer than a memory page ({}B), but this section is {}B long.", current_section_name, MEMORY_PAGE_SIZE, result.len());
+                    input_line_map.push(Diagnostic::new(ErrorNotificationKind::Error, "Section Too Long".to_string(), message, None));
                 }
+                // Remember where the old section ended up
+                placed_sections.push(PlacedSection { name: current_section_name.clone(), start: next_page_start - MEMORY_PAGE_SIZE, length: result.len() - (next_page_start - MEMORY_PAGE_SIZE) });
+
                 // Start a new section
                 // Find the section's name
                 current_section_name = input.sections.iter().find(| &x | x.clone().start_pos_bytes_original == actual_bytes_written as usize).unwrap().clone().name.clone();
@@ -56,106 +80,72 @@ pub fn perform_last_step(input: TokenizerResult, instructions: Vec<Instruction>,
                 let name = name.clone();
                 let args = args.clone();
 
-                // The format of the instruction
-                // 0 means register and 1 means immediate value
+                // Whether each argument is a register
                 // This is to keep instructions with multiple versions (e.g. add reg, reg; add reg, imm) apart
-                let mut format: Vec<bool> = Vec::new();
-
-                for arg in args.iter() {
-                    if matches!(arg, InstructionArgs::Register(_)) {
-                        format.push(false);
-                        continue;
-                    }
-                    format.push(true);
-                }
+                let format: Vec<bool> = args.iter().map(|x| matches!(x, InstructionArgs::Register(_))).collect();
 
                 // Find a matching instruction
+                // If multiple versions fit the arguments' format, prefer the first one all values fit into.
                 let correctly_named_instructions = instructions.iter().filter(|&x | x.name == name).collect::<Vec<&Instruction>>();
-                let instruction = correctly_named_instructions.iter().find(|&x| x.format == format);
+                let matching_instructions = correctly_named_instructions.iter().filter(|&x| x.format.iter().map(|y| y.is_register()).collect::<Vec<bool>>() == format).collect::<Vec<&&Instruction>>();
+                let instruction = matching_instructions.iter().find(|&x| args.iter().zip(x.format.iter()).all(|(arg, kind)| argument_fits(arg, kind))).or(matching_instructions.first());
 
                 if instruction.is_none(){
                     // Print an error and add the format
-                    let mut error = format!("Instruction named '{}' with format (", name);
-                    let mut error_format_string = String::new();
-                    for format in format.iter() {
-                        // If there is something in the format string already, print a colon
-                        if error_format_string != String::new(){
-                            error_format_string.push_str(", ");
-                        }
-                        if *format {
-                            error_format_string += "Immediate Value";
-                        } else {
-                            error_format_string += "Register";
-                        }
-                    }
-
-                    error += error_format_string.as_str();
-                    error += ") doesn't exist.";
+                    let format_string = format.iter().map(|&x| if x { "Register" } else { "Immediate Value" }).collect::<Vec<&str>>().join(", ");
+                    let mut error = format!("Instruction named '{}' with format ({}) doesn't exist.", name, format_string);
 
                     // Add other available formats if possible
                     if !correctly_named_instructions.is_empty() {
                         error += "\nBut there are instructions with the same name available in other formats: ";
                         for x in correctly_named_instructions.clone(){
-                            error += "(";
-                            let mut error_format_string = String::new();
-                            for format in x.format.iter() {
-                                // If there is something in the format string already, print a colon
-                                if error_format_string != String::new(){
-                                    error_format_string.push_str(", ");
-                                }
-                                if *format {
-                                    error_format_string += "Imm. Value";
-                                } else {
-                                    error_format_string += "Register";
-                                }
-                            }
-                            error_format_string += ")  ";
-                            error += error_format_string.as_str();
+                            error += format!("({})  ", x.format.iter().map(|y| y.to_string()).collect::<Vec<String>>().join(", ")).as_str();
                         }
                         error += ".";
                     }
 
 
-                    input_line_map.print_notification(ErrorNotificationKind::Error, i as u32, Some(0), "No Such Instruction".to_string(), error.to_string());
+                    input_line_map.report(ErrorNotificationKind::Error, i as u32, Some(0), "No Such Instruction".to_string(), error.to_string());
                     continue;
                 }
 
                 let instruction = instruction.unwrap();
 
                 let op_code: u16 = instruction.op_code.into();
-                let mut reg0 = 0u8;
-                let mut reg1 = 0u8;
-                let mut immediate = 0u16;
+                let mut values: Vec<Option<i64>> = Vec::new();
                 let mut global: Option<String> = None;
 
-                for x in args.iter().enumerate() {
-                    let arg = x.1.clone();
-                    let i = x.0.clone();
-
-                    match arg{
-                        InstructionArgs::Register(register) => {
-                            if i == 0{
-                                reg0 = register;
-                            } else {
-                                reg1 = register;
-                            }
-                        }
-
-                        InstructionArgs::Immediate(value) => {
-                            immediate = value;
-                        }
-
+                for (operand_index, (arg, kind)) in args.iter().zip(instruction.format.iter()).enumerate() {
+                    let value = match arg {
+                        InstructionArgs::Register(register) => *register as i64,
+                        InstructionArgs::Immediate(value) => *value as i64,
                         InstructionArgs::Global(name) => {
+                            // Filled in later
                             global = Some(name.clone());
+                            values.push(None);
+                            continue;
                         }
+                    };
+
+                    if !kind.accepts(value) {
+                        let (min, max) = kind.range();
+                        let value_string = if kind.is_register() { format!("x{}", value) } else { value.to_string() };
+                        let range_string = if kind.is_register() { format!("x{} to x{}", min, max) } else { format!("{} to {}", min, max) };
+
+                        // The operands' tokens come right after the instruction's name.
+                        input_line_map.report(ErrorNotificationKind::Error, i as u32, Some(1 + operand_index as u32), "Operand Out Of Range".to_string(), format!("Operand {} of '{}' is a {}, so {} isn't allowed ({} only).", operand_index + 1, name, kind, value_string, range_string));
+                        values.push(None);
+                        continue;
                     }
+
+                    values.push(Some(value));
                 }
 
                 // The instruction ignoring the global constant.
-                let mut instruction_coded: u32 = (op_code as u32) << 23;
-                instruction_coded |= (reg0 as u32) << 18;
-                instruction_coded |= (reg1 as u32) << 13;
-                instruction_coded |= immediate as u32;
+                let instruction_coded = ENCODING.encode(op_code, &instruction.format, &values);
+
+                // Decoding (e.g. in smiscvm) uses the same description, so this should always give back what was put in.
+                debug_assert!(ENCODING.decode_op_code(instruction_coded) == op_code && ENCODING.decode_operands(instruction_coded, &instruction.format).iter().zip(values.iter()).all(|(x, y)| y.is_none_or(|y| y == *x)));
 
                 append_u32_to_vec(&mut result, &mut actual_bytes_written, instruction_coded);
 
@@ -171,13 +161,31 @@ pub fn perform_last_step(input: TokenizerResult, instructions: Vec<Instruction>,
         }
     }
 
+    placed_sections.push(PlacedSection { name: current_section_name.clone(), start: next_page_start - MEMORY_PAGE_SIZE, length: result.len() - (next_page_start - MEMORY_PAGE_SIZE) });
+
     // No output line map needed here as the output is binary,
     // except for error-throwing purposes.
     let mut output_line_map = LineMap::new();
-    output_line_map.warnings_count = input_line_map.warnings_count;
-    output_line_map.errors_count = input_line_map.errors_count;
+    output_line_map.carry_over(&input_line_map);
+
+    (result, placed_sections, output_line_map)
+}
+
+/// Whether an argument fits into an operand of the given kind (globals are resolved later, so they always do).
+fn argument_fits(arg: &InstructionArgs, kind: &OperandKind) -> bool {
+    match arg {
+        InstructionArgs::Register(register) => kind.accepts(*register as i64),
+        InstructionArgs::Immediate(value) => kind.accepts(*value as i64),
+        InstructionArgs::Global(_) => true,
+    }
+}
 
-    (result, output_line_map)
+/// A section's position in the final binary.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlacedSection {
+    pub name: String,
+    pub start: usize,       // In bytes from the start of the binary (always the start of a memory page)
+    pub length: usize,      // In bytes, without the zero fill up to the next page
 }
 
 fn append_u8_to_vec(x: &mut Vec<u8>, size: &mut u32, data: u8){
@@ -0,0 +1,121 @@
+// The command line interface of smiscasm.
+
+use clap::{Parser, Subcommand};
+use crate::library::library::LibCommand;
+use crate::output::diagnostics::MessageFormat;
+use crate::output::dot::CfgFormat;
+use crate::output::output::OutputFormat;
+use crate::output::table::TableFormat;
+
+#[derive(Debug, PartialEq, Parser)]
+pub struct ArgumentList{
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+
+    pub file: Option<String>,
+
+    #[clap(short, long)]
+    pub help: bool,                                 // -h or --help
+
+    #[clap(long)]
+    pub instruction_help: Option<Option<String>>,   // --instruction-help
+
+    #[clap(long)]
+    pub list_instructions: bool,                    // --list-instructions
+
+    #[clap(short, long)]
+    pub output_name: Option<Option<String>>,        // -o or --output
+
+    #[clap(short, long, num_args = 0..=1)]
+    pub get_micro_operation: Option<Option<String>>,// --get-micro-operation
+
+    #[clap(short, long)]
+    pub generate_instruction_table: bool,           // --generate-instructions-table
+
+    #[clap(long, value_enum)]
+    pub format: Option<OutputFormat>,               // --format (bin if not set)
+
+    #[clap(long)]
+    pub base_address: Option<String>,               // --base-address
+
+    #[clap(long)]
+    pub split: bool,                                // --split (one output file per section)
+
+    #[clap(long)]
+    pub output_dir: Option<String>,                 // --output-dir (for the instruction table)
+
+    #[clap(long, value_enum)]
+    pub table_format: Option<TableFormat>,          // --table-format (raw if not set)
+
+    #[clap(long)]
+    pub combined: bool,                             // --combined (one 64-bit-wide instruction table)
+
+    #[clap(long, num_args = 2, value_names = ["OLD", "NEW"])]
+    pub diff_instruction_table: Option<Vec<String>>,// --diff-instruction-table (two instruction sets or directories with generated tables)
+
+    #[clap(long, num_args = 0..=1)]
+    pub opcode_map: Option<Option<String>>,         // --opcode-map (optionally with the name of the instruction to find an op-code for)
+
+    #[clap(long)]
+    pub timing_chart: Option<String>,               // --timing-chart (an instruction's name or op-code)
+
+    #[clap(long)]
+    pub svg: bool,                                  // --svg (also write the timing charts as SVG images)
+
+    #[clap(long)]
+    pub export_isa_docs: Option<String>,            // --export-isa-docs (write the instruction set's reference to a directory)
+
+    #[clap(long)]
+    pub isa: Option<String>,                        // --isa (load instructions from a directory instead of the built-in ones)
+
+    #[clap(short = 'W', value_name = "WARNING")]
+    pub warnings: Vec<String>,                      // -W no-<name> (turn a warning off) or -W error (warnings are errors)
+
+    #[clap(long)]
+    pub check_registers: bool,                      // --check-registers (warn about code breaking the register conventions)
+
+    #[clap(long)]
+    pub explain: Option<String>,                    // --explain (long-form explanation of a diagnostic code like E0013)
+
+    #[clap(long, value_enum)]
+    pub message_format: Option<MessageFormat>,      // --message-format (how errors & warnings are printed, human if not set)
+
+    #[clap(long)]
+    pub sarif: Option<String>,                      // --sarif (also write errors & warnings to a SARIF file)
+
+    #[clap(long, value_enum)]
+    pub emit_cfg: Option<CfgFormat>,                // --emit-cfg (also write the control flow graph, next to the input)
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum Command {
+    /// Manage downloaded libraries (pub-libs/ and the shared cache)
+    Lib {
+        #[clap(subcommand)]
+        action: LibCommand,
+    },
+
+    /// Rewrite .s files in the canonical layout (all in the working directory if none are given)
+    Fmt {
+        files: Vec<String>,
+
+        /// Don't write anything, fail if a file isn't formatted
+        #[clap(long)]
+        check: bool,
+    },
+
+    /// Run a language server for SMISC assembly (LSP over stdio)
+    Lsp,
+}
+
+impl ArgumentList{
+    pub fn new() -> ArgumentList{
+        ArgumentList{command: None, file: None, help: false, instruction_help: None, list_instructions: false, output_name: None, generate_instruction_table: false, get_micro_operation: None, format: None, base_address: None, split: false, output_dir: None, table_format: None, combined: false, diff_instruction_table: None, opcode_map: None, timing_chart: None, svg: false, export_isa_docs: None, isa: None, message_format: None, sarif: None, explain: None, warnings: Vec::new(), check_registers: false, emit_cfg: None}
+    }
+
+    /// Checks whether the current amount of data is enough (0) or the file name is missing (1)
+    pub fn needs_input_file(&self) -> bool{
+        let is_ok = self.help || self.command.is_some() || self.generate_instruction_table || self.file.is_some() || self.get_micro_operation.is_some() || self.instruction_help.is_some() || self.list_instructions || self.export_isa_docs.is_some() || self.timing_chart.is_some() || self.opcode_map.is_some() || self.diff_instruction_table.is_some() || self.explain.is_some();
+        !is_ok
+    }
+}
@@ -3,6 +3,12 @@ use crate::util::line_mapping::CodeInterpretationMode;
 /// Where to store public libraries on download (relative path)
 pub const PUBLIC_LIBS_DIR: &str = "pub-libs/";
 
+/// Where to store public libraries shared between projects (relative to the user's cache directory)
+pub const SHARED_LIBS_DIR: &str = "smiscasm/pub-libs/";
+
+/// The file in each library cache directory that remembers sources, hashes & download times
+pub const LIBRARY_INDEX_FILE_NAME: &str = ".index";
+
 
 /// Metadata about some libraries
 pub const KNOWN_PUBLIC_LIBS: [(&str, &str, &str); 2] = [
@@ -19,3 +25,6 @@ pub const MEMORY_PAGE_SIZE: usize = 4096;
 /// The mode (whether it expects data/code) the assembler is in when none have been specified
 pub const DEFAULT_MODE: CodeInterpretationMode = CodeInterpretationMode::None;
 
+
+/// The project manifest (optional, in the working directory)
+pub const MANIFEST_FILE_NAME: &str = "smiscasm.manifest";
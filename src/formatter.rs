@@ -0,0 +1,263 @@
+// `smiscasm fmt`: rewrites SMISC sources into one canonical layout.
+// Labels, !includes & assembler commands start at column 0, instructions & data are indented, operands are separated by ", ",
+// trailing comments of neighbouring lines are aligned and x31 is always written as sp.
+// Formatting never changes what's assembled.
+
+use std::fs;
+use colorize::AnsiColor;
+use crate::util::exit::{exit, ExitCode};
+
+const INDENT: &str = "    ";
+
+/// Assembler commands that produce data and are therefore indented like instructions.
+const DATA_COMMANDS: [&str; 3] = ["ascii", "stc", "incbin"];
+
+enum Line {
+    Blank,
+    Comment(String),
+    Code { text: String, indented: bool, comment: Option<String> },
+}
+
+/// Split at the first '#' (like the assembler does, even in strings).
+fn split_comment(line: &str) -> (&str, Option<String>) {
+    match line.find('#') {
+        Some(index) => (&line[..index], Some(line[index..].trim_end().to_string())),
+        None => (line, None),
+    }
+}
+
+/// Replace whitespace (outside of strings) with single spaces.
+fn collapse_whitespace(text: &str) -> String {
+    let mut result = String::new();
+    let mut in_string = false;
+    let mut had_whitespace = false;
+    let mut last_character = ' ';
+
+    for char in text.trim().chars() {
+        // Escaped quotes don't end strings (like in the splitter)
+        if char == '"' && last_character != '\\' { in_string = !in_string; }
+        last_character = char;
+
+        if !in_string && char.is_whitespace() {
+            had_whitespace = true;
+            continue;
+        }
+
+        if had_whitespace { result.push(' '); }
+        had_whitespace = false;
+        result.push(char);
+    }
+
+    result
+}
+
+/// Operands separated by ", " (commas in strings aren't separators). Registers are only normalized in instructions.
+fn format_operands(text: &str, normalize_registers: bool) -> String {
+    let mut operands = vec![String::new()];
+    let mut in_string = false;
+    let mut last_character = ' ';
+
+    for char in text.chars() {
+        if char == '"' && last_character != '\\' { in_string = !in_string; }
+        last_character = char;
+
+        if char == ',' && !in_string {
+            operands.push(String::new());
+            continue;
+        }
+
+        operands.last_mut().unwrap().push(char);
+    }
+
+    operands.iter().map(|x| {
+        let operand = collapse_whitespace(x);
+        if normalize_registers && operand == "x31" { "sp".to_string() } else { operand }
+    }).collect::<Vec<String>>().join(", ")
+}
+
+/// The canonical form of a line's code (without comments) and whether it's indented.
+fn format_statement(code: &str) -> (String, bool) {
+    let code = code.trim();
+
+    if let Some(argument) = code.strip_prefix("!include") {
+        return (format!("!include {}", argument.trim()), false);
+    }
+
+    // Labels
+    if let Some(name) = code.strip_suffix(':') && !name.trim().contains(char::is_whitespace) {
+        return (format!("{}:", name.trim()), false);
+    }
+
+    // Assembler commands & constants
+    if let Some(rest) = code.strip_prefix('.') {
+        let rest = rest.trim_start();
+        let name_length = rest.find(|x: char| x.is_whitespace() || x == '"').unwrap_or(rest.len());
+        let (name, arguments) = rest.split_at(name_length);
+
+        // Modes are case-insensitive, but written in lowercase
+        let mut arguments = format_operands(arguments, false);
+        if name == "mode" { arguments = arguments.to_lowercase(); }
+
+        let text = if arguments.is_empty() { format!(".{}", name) } else { format!(".{} {}", name, arguments) };
+        return (text, DATA_COMMANDS.contains(&name));
+    }
+
+    // Instructions
+    let (name, operands) = code.split_once(char::is_whitespace).unwrap_or((code, ""));
+    let operands = format_operands(operands, true);
+
+    if operands.is_empty() { (name.to_string(), true) } else { (format!("{} {}", name, operands), true) }
+}
+
+fn parse_line(line: &str) -> Line {
+    let (code, comment) = split_comment(line);
+
+    if code.trim().is_empty() {
+        return match comment {
+            Some(comment) => Line::Comment(comment),
+            None => Line::Blank,
+        };
+    }
+
+    let (text, indented) = format_statement(code);
+    Line::Code { text, indented, comment }
+}
+
+/// The code in the canonical layout.
+pub fn format_code(code: &str) -> String {
+    let mut lines = Vec::new();
+
+    // At most one blank line in a row and none at the start
+    for line in code.lines().map(parse_line) {
+        if matches!(line, Line::Blank) && matches!(lines.last(), None | Some(Line::Blank)) { continue; }
+        lines.push(line);
+    }
+
+    while matches!(lines.last(), Some(Line::Blank)) { lines.pop(); }
+
+    // Comments on their own line are indented like the code after them (or before them at the end).
+    let indents = (0..lines.len()).map(|i| {
+        let is_indented = |line: &Line| match line { Line::Code { indented, .. } => Some(*indented), _ => None };
+
+        lines[i..].iter().find_map(is_indented).or_else(|| lines[..i].iter().rev().find_map(is_indented)).unwrap_or(false)
+    }).collect::<Vec<bool>>();
+
+    let mut result = lines.iter().zip(indents.iter()).map(|(line, indented)| {
+        let indent = if *indented { INDENT } else { "" };
+
+        match line {
+            Line::Blank => String::new(),
+            Line::Comment(comment) => format!("{}{}", indent, comment),
+            Line::Code { text, .. } => format!("{}{}", indent, text),
+        }
+    }).collect::<Vec<String>>();
+
+    // Align trailing comments of neighbouring lines
+    let mut i = 0;
+    while i < lines.len() {
+        let has_trailing_comment = |line: &Line| matches!(line, Line::Code { comment: Some(_), .. });
+
+        if !has_trailing_comment(&lines[i]) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < lines.len() && has_trailing_comment(&lines[i]) { i += 1; }
+
+        let width = result[start..i].iter().map(|x| x.chars().count()).max().unwrap();
+
+        for j in start..i {
+            if let Line::Code { comment: Some(comment), .. } = &lines[j] {
+                let padding = width - result[j].chars().count() + 1;
+                result[j] += &format!("{}{}", " ".repeat(padding), comment);
+            }
+        }
+    }
+
+    if result.is_empty() { return String::new(); }
+
+    result.join("\n") + "\n"
+}
+
+/// Format files in place (all .s files in the working directory if none are given).
+/// With `check`, nothing is written and it fails if a file isn't formatted.
+pub fn run_fmt(files: Vec<String>, check: bool) {
+    let mut files = files;
+
+    if files.is_empty() {
+        let entries = fs::read_dir(".").map(|x| x.filter_map(|x| x.ok()).map(|x| x.path()).collect::<Vec<_>>()).unwrap_or_default();
+        files = entries.iter().filter(|x| x.is_file() && x.extension().is_some_and(|x| x == "s")).filter_map(|x| x.to_str().map(|x| x.trim_start_matches("./").to_string())).collect();
+        files.sort();
+    }
+
+    let mut unformatted = Vec::new();
+
+    for file in files.iter() {
+        let Ok(code) = fs::read_to_string(file) else {
+            exit(format!("Couldn't read {}.", file), ExitCode::ReadWriteError);
+            return;
+        };
+
+        let formatted = format_code(&code);
+        if formatted == code { continue; }
+
+        unformatted.push(file.clone());
+
+        if check {
+            println!("{} {}", "Not formatted:".yellow(), file);
+        } else if fs::write(file, formatted).is_err() {
+            exit(format!("Couldn't write {}.", file), ExitCode::ReadWriteError);
+        } else {
+            println!("{} {}", "Formatted".green(), file);
+        }
+    }
+
+    if check && !unformatted.is_empty() {
+        exit(format!("{} of {} files aren't formatted, run smiscasm fmt to format them.", unformatted.len(), files.len()), ExitCode::Other);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assembler::{assemble, AssembleOptions};
+    use crate::formatter::format_code;
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::util::file_system::MemoryFileSystem;
+
+    const MESSY: &str = "\n\n  . section   \"CODE\"\n.mode TEXT\n.len   13   # The length\n\t_main :\n\tadrp x0,msg@PAGE # Load it\n        add  x0 ,  msg@PAGEOFF+len\n  mov x31,x0\n# Done\n\thlt\n\n\n\n.section \"DATA\"\n.mode data\nmsg:\n  .ascii   \"Hello, world!\"\n\t# The end\n\n";
+
+    const FORMATTED: &str = ".section \"CODE\"\n.mode text\n.len 13 # The length\n_main:\n    adrp x0, msg@PAGE # Load it\n    add x0, msg@PAGEOFF+len\n    mov sp, x0\n    # Done\n    hlt\n\n.section \"DATA\"\n.mode data\nmsg:\n    .ascii \"Hello, world!\"\n    # The end\n";
+
+    #[test]
+    fn test_format_code() {
+        assert_eq!(format_code(MESSY), FORMATTED);
+        assert_eq!(format_code(FORMATTED), FORMATTED);
+        assert_eq!(format_code("\n\n"), "");
+    }
+
+    #[test]
+    fn test_align_comments() {
+        let code = ".a 1 # one\n.long_name 2 # two\n_main:\n\thlt\t# stop\n";
+
+        assert_eq!(format_code(code), ".a 1         # one\n.long_name 2 # two\n_main:\n    hlt # stop\n");
+    }
+
+    #[tokio::test]
+    async fn test_format_keeps_binary() {
+        let files = MemoryFileSystem::new();
+
+        let mut sources = ["test_code/bsc_test.s", "test_code/test_code.s", "test_code/yet_another_test_code.s", "test_code/sp.s", "test_code/lsh.s"].iter().map(|x| (x.to_string(), std::fs::read_to_string(x).unwrap())).collect::<Vec<(String, String)>>();
+
+        // Whitespace in strings with escaped quotes is kept
+        sources.push(("escaped.s".to_string(), ".section \"DATA\"\n.mode data\nmsg:\n    .ascii  \"q \\\"x  y\\\" z\"\n".to_string()));
+
+        for (file, code) in sources {
+            let original = assemble(code.clone(), get_all_instructions(), &files, AssembleOptions::new(file.clone())).await.unwrap();
+            let formatted = assemble(format_code(&code), get_all_instructions(), &files, AssembleOptions::new(file.clone())).await.unwrap();
+
+            assert_eq!(original.image, formatted.image, "{}", file);
+        }
+    }
+}
@@ -1,15 +1,58 @@
 // Termimad is for Markdown formatting in the terminal.
 use termimad;
 use include_dir::{include_dir, Dir};
-use crate::ArgumentList;
+use crate::cli::{ArgumentList, Command};
+use crate::help::reference::{describe_instruction, list_instructions};
+use crate::instruction::instruction::get_all_instructions;
+use crate::util::diagnostic::DIAGNOSTIC_CODES;
 use crate::util::exit::{exit, ExitCode};
 
 const HELP_STRING: &str = include_str!("help.md");
 const COMMAND_HELP_FILES: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/help/commands");
-const INSTRUCTION_HELP_FILES: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/help/instructions");
+const EXPLANATION_FILES: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/help/explanations");
 
 
 pub fn print_help(arguments: ArgumentList) {
+    if matches!(arguments.command, Some(Command::Fmt { .. })) {
+        print_help_file("fmt".to_string());
+        return;
+    }
+
+    if arguments.command == Some(Command::Lsp) {
+        print_help_file("lsp".to_string());
+        return;
+    }
+
+    if arguments.command.is_some() {
+        print_help_file("lib".to_string());
+        return;
+    }
+
+    if arguments.isa.is_some() {
+        print_help_file("isa".to_string());
+        return;
+    }
+
+    if arguments.diff_instruction_table.is_some() {
+        print_help_file("diff-instruction-table".to_string());
+        return;
+    }
+
+    if arguments.opcode_map.is_some() {
+        print_help_file("opcode-map".to_string());
+        return;
+    }
+
+    if arguments.timing_chart.is_some() || arguments.svg {
+        print_help_file("timing-chart".to_string());
+        return;
+    }
+
+    if arguments.export_isa_docs.is_some() {
+        print_help_file("export-isa-docs".to_string());
+        return;
+    }
+
     if arguments.generate_instruction_table{
         print_help_file("generate-instruction-table".to_string());
         return;
@@ -25,6 +68,41 @@ pub fn print_help(arguments: ArgumentList) {
         return;
     }
 
+    if arguments.list_instructions {
+        print_help_file("list-instructions".to_string());
+        return;
+    }
+
+    if arguments.format.is_some() || arguments.base_address.is_some() || arguments.split {
+        print_help_file("format".to_string());
+        return;
+    }
+
+    if !arguments.warnings.is_empty() {
+        print_help_file("warnings".to_string());
+        return;
+    }
+
+    if arguments.emit_cfg.is_some() {
+        print_help_file("emit-cfg".to_string());
+        return;
+    }
+
+    if arguments.check_registers {
+        print_help_file("check-registers".to_string());
+        return;
+    }
+
+    if arguments.explain.is_some() {
+        print_help_file("explain".to_string());
+        return;
+    }
+
+    if arguments.message_format.is_some() || arguments.sarif.is_some() {
+        print_help_file("message-format".to_string());
+        return;
+    }
+
     if arguments.output_name.is_some() {
         print_help_file("output".to_string());
     }
@@ -33,13 +111,29 @@ pub fn print_help(arguments: ArgumentList) {
 }
 
 pub fn print_instruction_help(named: String){
-    if let Some(contents) = INSTRUCTION_HELP_FILES.get_file(named.clone() + ".md"){
-        println!("{}", termimad::inline(contents.contents_utf8().unwrap()));
+    if let Some(contents) = describe_instruction(&named, &get_all_instructions()){
+        println!("{}", termimad::text(&contents));
     }else{
-        exit(format!("There is no help file for an instruction named {}.", named), ExitCode::BadArgument);
+        exit(format!("There is neither a definition nor a help file for an instruction named {}.", named), ExitCode::BadArgument);
     }
 }
 
+/// The long-form explanation of a diagnostic code (like E0013).
+pub fn print_explanation(code: String) {
+    let code = code.to_uppercase();
+
+    if let Some(contents) = EXPLANATION_FILES.get_file(format!("{}.md", code)) {
+        println!("{}", termimad::text(contents.contents_utf8().unwrap()));
+    }else{
+        let codes = DIAGNOSTIC_CODES.iter().map(|x| x.0).collect::<Vec<&str>>().join(", ");
+        exit(format!("There is no diagnostic with the code {}. Known codes are: {}.", code, codes), ExitCode::BadArgument);
+    }
+}
+
+pub fn print_instruction_list() {
+    println!("{}", termimad::text(&list_instructions(&get_all_instructions())));
+}
+
 fn print_help_file(name: String) {
     let contents = COMMAND_HELP_FILES.get_file(format!("{}.md", name));
 
@@ -48,4 +142,20 @@ fn print_help_file(name: String) {
     }else{
         exit(format!("Tried to open help file commands/{}, but failed.", name), ExitCode::Internal);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::help::help::EXPLANATION_FILES;
+    use crate::util::diagnostic::DIAGNOSTIC_CODES;
+
+    #[test]
+    fn test_explanations() {
+        for (code, title) in DIAGNOSTIC_CODES.iter() {
+            let contents = EXPLANATION_FILES.get_file(format!("{}.md", code)).unwrap().contents_utf8().unwrap();
+            assert!(contents.starts_with(&format!("**{} - {}**", code, title)), "{}", code);
+        }
+
+        assert_eq!(EXPLANATION_FILES.files().count(), DIAGNOSTIC_CODES.len());
+    }
+}
@@ -1 +1,2 @@
-pub mod help;
\ No newline at end of file
+pub mod help;
+pub mod reference;
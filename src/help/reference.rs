@@ -0,0 +1,148 @@
+// Help for instructions, generated from their definitions and merged with the hand-written help files (if there are any).
+
+use include_dir::{include_dir, Dir};
+use crate::instruction::call_word::flags_description;
+use crate::instruction::instruction::Instruction;
+use crate::instruction::micro_operation::active_micro_operations;
+use crate::instruction::validation::MAX_STAGES;
+
+const INSTRUCTION_HELP_FILES: Dir = include_dir!("$CARGO_MANIFEST_DIR/src/help/instructions");
+
+/// The hand-written help for an instruction.
+pub fn instruction_prose(name: &str) -> Option<&'static str> {
+    INSTRUCTION_HELP_FILES.get_file(format!("{}.md", name)).and_then(|x| x.contents_utf8())
+}
+
+/// The short description from the first line of an instruction's help file (e.g. "Address Page" for adrp).
+pub fn instruction_summary(name: &str) -> Option<String> {
+    let first_line = instruction_prose(name)?.lines().next()?.trim().trim_matches('*');
+
+    first_line.split_once(" - ").map(|x| x.1.trim().to_string())
+}
+
+/// The names of the micro operations for every stage the CU goes through with the given flags (up to the first RESET_MICRO).
+pub fn executed_stages(instruction: &Instruction, flags: u16) -> Vec<Vec<String>> {
+    let mut stages: Vec<Vec<String>> = Vec::new();
+
+    for stage in 0..MAX_STAGES {
+        let Some(control_word) = instruction.resolve_stage(stage, flags) else { break; };
+        let names = active_micro_operations(control_word).iter().map(|x| x.name.clone()).collect::<Vec<String>>();
+        let resets = names.iter().any(|x| x == "RESET_MICRO");
+
+        stages.push(names);
+
+        if resets { break; }
+    }
+
+    stages
+}
+
+/// Help for every instruction with the name (as markdown), None if there's neither a definition nor a help file.
+pub fn describe_instruction(name: &str, instructions: &[Instruction]) -> Option<String> {
+    let mut definitions = instructions.iter().filter(|x| x.name == name).collect::<Vec<&Instruction>>();
+    definitions.sort_by_key(|x| x.op_code);
+
+    let prose = instruction_prose(name);
+
+    if definitions.is_empty() && prose.is_none() { return None; }
+
+    let mut result = match prose {
+        Some(prose) => format!("{}\n\n", prose.trim_end()),
+        None => format!("**`{}`**\n\n", name),
+    };
+
+    if definitions.is_empty() {
+        result.push_str("*There is no definition for this instruction in the current instruction set.*\n");
+        return Some(result);
+    }
+
+    result.push_str("**Definitions**\n");
+
+    for instruction in definitions.iter() {
+        let kinds = instruction.format.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        let kinds = if kinds.is_empty() { "no operands".to_string() } else { kinds.join(", ") };
+        let usage = format!("{} {}", instruction.name, instruction.format_notation());
+
+        result.push_str(&format!("\n**`{}`** - op-code 0x{:02X} ({})\n", usage.trim_end(), instruction.op_code, kinds));
+
+        for flags in instruction.flag_versions() {
+            let stages = executed_stages(instruction, flags);
+
+            result.push_str(&format!("\n*{}* - {} stage(s)\n", flags_description(flags), stages.len()));
+
+            for (i, names) in stages.iter().enumerate() {
+                let names = if names.is_empty() { "-".to_string() } else { names.join(", ") };
+
+                result.push_str(&format!("* {}: {}\n", i, names));
+            }
+        }
+    }
+
+    Some(result)
+}
+
+/// A list of every instruction in the instruction set (as markdown).
+pub fn list_instructions(instructions: &[Instruction]) -> String {
+    let mut sorted = instructions.iter().collect::<Vec<&Instruction>>();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name).then(a.op_code.cmp(&b.op_code)));
+
+    let mut result = format!("**Instructions** ({})\n\n", sorted.len());
+
+    for instruction in sorted.iter() {
+        let usage = format!("{} {}", instruction.name, instruction.format_notation());
+        let summary = instruction_summary(&instruction.name).map(|x| format!(" - {}", x)).unwrap_or_default();
+
+        result.push_str(&format!("* `{}` (0x{:02X}){}\n", usage.trim_end(), instruction.op_code, summary));
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::help::reference::{describe_instruction, executed_stages, instruction_summary, list_instructions};
+    use crate::instruction::call_word::ZF_FLAG;
+    use crate::instruction::instruction::{get_all_instructions, Instruction};
+
+    #[test]
+    fn test_instruction_summary() {
+        assert_eq!(instruction_summary("adrp"), Some("Address Page".to_string()));
+        assert_eq!(instruction_summary("nand"), None);
+    }
+
+    #[test]
+    fn test_executed_stages() {
+        let instruction = Instruction::from_string("a\nx i\n01\n@STAGE\nPC_OUT\n@STAGE\nRESET_MICRO\n@VERSION\n@ZF\nPC_IN\n@STAGE\nRESET_MICRO".to_string());
+
+        assert_eq!(executed_stages(&instruction, 0), vec![vec![], vec!["PC_OUT".to_string()], vec!["RESET_MICRO".to_string()]]);
+        assert_eq!(executed_stages(&instruction, ZF_FLAG).len(), 4);
+    }
+
+    #[test]
+    fn test_describe_instruction() {
+        let instructions = get_all_instructions();
+
+        // Generated from the definitions only
+        let nand = describe_instruction("nand", &instructions).unwrap();
+        assert!(nand.contains("**`nand x x`** - op-code 0x55 (Register, Register)"));
+
+        // Merged with the help file
+        let add = describe_instruction("add", &instructions).unwrap();
+        assert!(add.starts_with("**`add` - Add registers"));
+        assert!(add.contains("**`add x i`** - op-code 0xA0"));
+        assert!(add.contains("**`add x x`** - op-code 0xA1"));
+
+        assert_eq!(describe_instruction("nonexistent", &instructions), None);
+    }
+
+    #[test]
+    fn test_list_instructions() {
+        let instructions = get_all_instructions();
+        let list = list_instructions(&instructions);
+
+        assert_eq!(list.lines().filter(|x| x.starts_with("* ")).count(), instructions.len());
+        assert!(list.contains("* `hlt` (0xFA)"));
+        assert!(list.contains("* `adrp x i` (0xC3) - Address Page"));
+    }
+}
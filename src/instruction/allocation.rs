@@ -0,0 +1,165 @@
+// Which op-codes are in use, which are free and which collide.
+
+use crate::instruction::instruction::Instruction;
+
+/// The amount of op-codes suggested for a new instruction
+const SUGGESTION_COUNT: usize = 3;
+
+fn usage(instruction: &Instruction) -> String {
+    format!("{} {}", instruction.name, instruction.format_notation()).trim_end().to_string()
+}
+
+/// A message for every op-code used by more than one definition (naming all of their files).
+pub fn op_code_conflicts(definitions: &[(String, Instruction)]) -> Vec<String> {
+    let mut op_codes = definitions.iter().map(|x| x.1.op_code).collect::<Vec<u16>>();
+    op_codes.sort();
+    op_codes.dedup();
+
+    op_codes.iter().filter_map(|&op_code| {
+        let users = definitions.iter().filter(|x| x.1.op_code == op_code).collect::<Vec<&(String, Instruction)>>();
+
+        if users.len() < 2 { return None; }
+
+        let names = users.iter().map(|x| format!("{} ({})", usage(&x.1), x.0)).collect::<Vec<String>>();
+
+        Some(format!("Op-code 0x{:02X} is used by {}.", op_code, names.join(" and ")))
+    }).collect()
+}
+
+/// The ranges (first & last, inclusive) of op-codes between 0 and max nobody uses.
+pub fn free_ranges(used: &[u16], max: u16) -> Vec<(u16, u16)> {
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    let mut start: Option<u16> = None;
+
+    for op_code in 0..=max {
+        match (used.contains(&op_code), start) {
+            (false, None) => start = Some(op_code),
+            (true, Some(first)) => { ranges.push((first, op_code - 1)); start = None; }
+            _ => {}
+        }
+    }
+
+    if let Some(first) = start { ranges.push((first, max)); }
+
+    ranges
+}
+
+/// Free op-codes for a new instruction: the ones closest to the existing versions of the instruction (if there are any),
+/// otherwise the start of the largest free range.
+pub fn suggest_op_codes(used: &[u16], max: u16, related: &[u16]) -> Vec<u16> {
+    let mut free = (0..=max).filter(|x| !used.contains(x)).collect::<Vec<u16>>();
+
+    if related.is_empty() {
+        let Some(largest) = free_ranges(used, max).into_iter().max_by_key(|x| (x.1 - x.0, u16::MAX - x.0)) else { return Vec::new(); };
+
+        return (largest.0..=largest.1).take(SUGGESTION_COUNT).collect();
+    }
+
+    let distance = |x: &u16| related.iter().map(|y| x.abs_diff(*y)).min().unwrap_or(u16::MAX);
+    free.sort_by_key(|x| (distance(x), *x));
+    free.truncate(SUGGESTION_COUNT);
+
+    free
+}
+
+/// A report of the whole op-code space: used op-codes, conflicts, free ranges & suggestions (for a new version of `name` if given).
+pub fn op_code_map(definitions: &[(String, Instruction)], max: u16, name: Option<&str>) -> String {
+    let mut sorted = definitions.iter().collect::<Vec<&(String, Instruction)>>();
+    sorted.sort_by_key(|x| x.1.op_code);
+
+    let mut used = sorted.iter().map(|x| x.1.op_code).collect::<Vec<u16>>();
+    used.dedup();
+
+    let free = free_ranges(&used, max);
+    let width = sorted.iter().map(|x| usage(&x.1).len()).max().unwrap_or(0);
+
+    let mut result = format!("Op-codes 0x00-0x{:02X}: {} used, {} free\n\nUsed:\n", max, used.len(), max as usize + 1 - used.len());
+
+    for (file_name, instruction) in sorted.iter() {
+        result.push_str(&format!("  0x{:02X}  {:<width$}  {}\n", instruction.op_code, usage(instruction), file_name, width = width));
+    }
+
+    let conflicts = op_code_conflicts(definitions);
+
+    if !conflicts.is_empty() {
+        result.push_str("\nConflicts:\n");
+        conflicts.iter().for_each(|x| result.push_str(&format!("  {}\n", x)));
+    }
+
+    result.push_str("\nFree:\n");
+
+    for (first, last) in free.iter() {
+        if first == last {
+            result.push_str(&format!("  0x{:02X}\n", first));
+        } else {
+            result.push_str(&format!("  0x{:02X}-0x{:02X}  ({})\n", first, last, last - first + 1));
+        }
+    }
+
+    let related = sorted.iter().filter(|x| Some(x.1.name.as_str()) == name).map(|x| x.1.op_code).collect::<Vec<u16>>();
+    let suggestions = suggest_op_codes(&used, max, &related).iter().map(|x| format!("0x{:02X}", x)).collect::<Vec<String>>();
+
+    let target = match name {
+        Some(name) if !related.is_empty() => format!("another version of {}", name),
+        _ => "a new instruction".to_string(),
+    };
+
+    if suggestions.is_empty() {
+        result.push_str("\nThere are no free op-codes left.\n");
+    } else {
+        result.push_str(&format!("\nSuggested for {}: {}\n", target, suggestions.join(", ")));
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::allocation::{free_ranges, op_code_conflicts, op_code_map, suggest_op_codes};
+    use crate::instruction::instruction::{get_instruction_definitions, Instruction};
+
+    fn definition(file_name: &str, name: &str, op_code: u16) -> (String, Instruction) {
+        (file_name.to_string(), Instruction::new_with_op(name.to_string(), vec![], op_code, vec![]))
+    }
+
+    #[test]
+    fn test_op_code_conflicts() {
+        let definitions = vec![definition("a.txt", "a", 0xD2), definition("b.txt", "b", 0xD3), definition("c.txt", "c", 0xD2)];
+
+        assert_eq!(op_code_conflicts(&definitions), vec!["Op-code 0xD2 is used by a (a.txt) and c (c.txt).".to_string()]);
+
+        // The built-in instructions don't collide
+        assert!(op_code_conflicts(&get_instruction_definitions()).is_empty());
+    }
+
+    #[test]
+    fn test_free_ranges() {
+        assert_eq!(free_ranges(&[0, 3, 4, 9], 0xF), vec![(1, 2), (5, 8), (10, 15)]);
+        assert_eq!(free_ranges(&[], 3), vec![(0, 3)]);
+        assert!(free_ranges(&[0, 1], 1).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_op_codes() {
+        // Next to the existing versions
+        assert_eq!(suggest_op_codes(&[0xA0, 0xA1, 0xA2, 0xC0], 0x1FF, &[0xA0, 0xA1]), vec![0x9F, 0x9E, 0xA3]);
+
+        // The largest free range
+        assert_eq!(suggest_op_codes(&[0x01, 0x10], 0x14, &[]), vec![0x02, 0x03, 0x04]);
+
+        assert!(suggest_op_codes(&[0, 1], 1, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_op_code_map() {
+        let definitions = vec![definition("instructions/add_i.txt", "add", 0xA0), definition("instructions/sub_i.txt", "sub", 0xA0), definition("instructions/hlt.txt", "hlt", 0xFF)];
+        let map = op_code_map(&definitions, 0xFF, Some("hlt"));
+
+        assert!(map.starts_with("Op-codes 0x00-0xFF: 2 used, 254 free\n"));
+        assert!(map.contains("  0xA0  add  instructions/add_i.txt\n"));
+        assert!(map.contains("Op-code 0xA0 is used by add (instructions/add_i.txt) and sub (instructions/sub_i.txt)."));
+        assert!(map.contains("  0x00-0x9F  (160)\n  0xA1-0xFE  (94)\n"));
+        assert!(map.ends_with("Suggested for another version of hlt: 0xFE, 0xFD, 0xFC\n"));
+    }
+}
@@ -0,0 +1,17 @@
+// The call word the CU's decoding table is addressed with: the op-code, the flags and the stage.
+
+/// The call word's bits for the flags a stage's version can depend on
+pub const ZF_FLAG: u16 = 0x4000;
+pub const PM_FLAG: u16 = 0x8000;
+
+/// The call word's bits for the stage
+pub const STAGE_MASK: u16 = 0x001F;
+
+pub fn flags_description(flags: u16) -> String {
+    match (flags & ZF_FLAG != 0, flags & PM_FLAG != 0) {
+        (false, false) => "no flags".to_string(),
+        (true, false) => "@ZF".to_string(),
+        (false, true) => "@PM".to_string(),
+        (true, true) => "@ZF & @PM".to_string(),
+    }
+}
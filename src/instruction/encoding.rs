@@ -0,0 +1,204 @@
+// The instruction encoding, as declared by the @FIELD lines in the machine description.
+// Both encoding (assembling) and decoding use this, so they can't disagree.
+
+use std::sync::LazyLock;
+use crate::instruction::micro_operation::machine_description;
+use crate::instruction::operand::{OperandKind, REGISTER_BITS};
+use crate::util::exit::{exit, ExitCode};
+use crate::util::remove_comments::remove_comments_in_line;
+
+/// The width of an encoded instruction
+pub const INSTRUCTION_BITS: u8 = 32;
+
+pub static ENCODING: LazyLock<Encoding> = LazyLock::new(|| parse_encoding(machine_description()));
+
+/// A range of bits in an encoded instruction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Field {
+    pub shift: u8,
+    pub bits: u8,
+}
+
+impl Field {
+    pub fn mask(&self) -> u32 {
+        (((1u64 << self.bits) - 1) << self.shift) as u32
+    }
+
+    fn overlaps(&self, other: &Field) -> bool {
+        self.mask() & other.mask() != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Encoding {
+    pub op_code: Field,
+    pub registers: Vec<Field>,      // Register operands use them in order
+    pub immediates: Field,          // Immediate values are placed in here, the first one at the LSb
+}
+
+impl Encoding {
+    /// Where each operand goes (as a left shift), or None if the operands don't fit the encoding.
+    pub fn operand_shifts(&self, format: &[OperandKind]) -> Option<Vec<u8>> {
+        let mut used_registers: Vec<Field> = Vec::new();
+        let mut next_immediate_shift = self.immediates.shift;
+        let mut shifts: Vec<u8> = Vec::new();
+
+        for kind in format.iter() {
+            if kind.is_register() {
+                let field = *self.registers.get(used_registers.len())?;
+                used_registers.push(field);
+                shifts.push(field.shift);
+            } else {
+                shifts.push(next_immediate_shift);
+                next_immediate_shift += kind.bits();
+            }
+        }
+
+        // All immediate values have to be within their field and mustn't overlap the registers used.
+        let used_immediates = Field { shift: self.immediates.shift, bits: next_immediate_shift - self.immediates.shift };
+
+        if next_immediate_shift > self.immediates.shift + self.immediates.bits || used_registers.iter().any(|x| x.overlaps(&used_immediates)) {
+            return None;
+        }
+
+        Some(shifts)
+    }
+
+    /// The largest op-code the encoding allows.
+    pub fn max_op_code(&self) -> u16 {
+        ((1u32 << self.op_code.bits) - 1) as u16
+    }
+
+    /// Encode an instruction. Operands without a value (e.g. globals) are left empty.
+    pub fn encode(&self, op_code: u16, format: &[OperandKind], values: &[Option<i64>]) -> u32 {
+        let mut result = ((op_code as u32) << self.op_code.shift) & self.op_code.mask();
+
+        if let Some(shifts) = self.operand_shifts(format) {
+            for ((kind, value), shift) in format.iter().zip(values.iter()).zip(shifts.iter()) {
+                if let Some(value) = value {
+                    result |= kind.encode(*value) << shift;
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Get an encoded instruction's op-code.
+    pub fn decode_op_code(&self, word: u32) -> u16 {
+        ((word & self.op_code.mask()) >> self.op_code.shift) as u16
+    }
+
+    /// Get an encoded instruction's operands given its format (the opposite of `encode`).
+    pub fn decode_operands(&self, word: u32, format: &[OperandKind]) -> Vec<i64> {
+        let Some(shifts) = self.operand_shifts(format) else { return Vec::new(); };
+
+        format.iter().zip(shifts.iter()).map(|(kind, shift)| kind.decode(word >> shift)).collect()
+    }
+}
+
+/// Read the @FIELD lines of a machine description.
+pub fn parse_encoding(string: &str) -> Encoding {
+    let mut op_code: Option<Field> = None;
+    let mut registers: Vec<Field> = Vec::new();
+    let mut immediates: Option<Field> = None;
+
+    for line in string.lines() {
+        let line = remove_comments_in_line(line.to_string());
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+
+        if words.first() != Some(&"@FIELD") { continue; }
+
+        let field = match (words.len(), words.get(2).and_then(|x| x.parse::<u8>().ok()), words.get(3).and_then(|x| x.parse::<u8>().ok())) {
+            (4, Some(shift), Some(bits)) if bits > 0 && shift as u16 + bits as u16 <= INSTRUCTION_BITS as u16 => Field { shift, bits },
+            _ => {
+                exit(format!("Encoding field \"{}\" should consist of a kind, a shift and a width within {} bits.", line.trim(), INSTRUCTION_BITS), ExitCode::Other);
+                continue;
+            }
+        };
+
+        match words[1] {
+            "opcode" if op_code.is_none() => op_code = Some(field),
+            "imm" if immediates.is_none() => immediates = Some(field),
+            "reg" if field.bits >= REGISTER_BITS => registers.push(field),
+            "reg" => exit(format!("Register fields need at least {} bits.", REGISTER_BITS), ExitCode::Other),
+            "opcode" | "imm" => exit(format!("There can only be one {} field.", words[1]), ExitCode::Other),
+            _ => exit(format!("Unknown encoding field kind \"{}\".\nShould be opcode, reg or imm.", words[1]), ExitCode::Other),
+        }
+    }
+
+    if op_code.is_none() || immediates.is_none() {
+        exit("The machine description needs both an opcode and an imm field.".to_string(), ExitCode::Other);
+    }
+
+    let op_code = op_code.unwrap();
+
+    // Registers may share bits with immediate values (depending on the instruction), but nothing may share bits with the op-code or another register.
+    for (i, register) in registers.iter().enumerate() {
+        if register.overlaps(&op_code) || registers[..i].iter().any(|x| x.overlaps(register)) {
+            exit(format!("Register field at bit {} overlaps with another register or the op-code.", register.shift), ExitCode::Other);
+        }
+    }
+
+    if immediates.unwrap().overlaps(&op_code) {
+        exit("The imm field overlaps with the op-code.".to_string(), ExitCode::Other);
+    }
+
+    Encoding { op_code, registers, immediates: immediates.unwrap() }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::encoding::{parse_encoding, Field};
+    use crate::instruction::operand::OperandKind;
+
+    fn format(string: &str) -> Vec<OperandKind> {
+        string.split_whitespace().map(|x| OperandKind::from_string(x).unwrap()).collect()
+    }
+
+    #[test]
+    fn test_parse_encoding() {
+        let encoding = parse_encoding("PC_OUT 0 high driver\n@FIELD opcode 23 9 # op\n@FIELD reg 18 5\n@FIELD imm 0 18");
+
+        assert_eq!(encoding.op_code, Field { shift: 23, bits: 9 });
+        assert_eq!(encoding.registers, vec![Field { shift: 18, bits: 5 }]);
+        assert_eq!(encoding.immediates, Field { shift: 0, bits: 18 });
+    }
+
+    #[test]
+    fn test_operand_shifts() {
+        let encoding = parse_encoding("@FIELD opcode 23 9\n@FIELD reg 18 5\n@FIELD reg 13 5\n@FIELD reg 8 5\n@FIELD imm 0 23");
+
+        // The layout smiscasm has always used
+        assert_eq!(encoding.operand_shifts(&format("x x")), Some(vec![18, 13]));
+        assert_eq!(encoding.operand_shifts(&format("x i")), Some(vec![18, 0]));
+
+        assert_eq!(encoding.operand_shifts(&format("u4 x u8")), Some(vec![0, 18, 4]));
+        assert_eq!(encoding.operand_shifts(&format("x u18")), Some(vec![18, 0]));
+
+        // Overlapping or too many registers
+        assert_eq!(encoding.operand_shifts(&format("x x u14")), None);
+        assert_eq!(encoding.operand_shifts(&format("x x x x")), None);
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let encoding = parse_encoding("@FIELD opcode 23 9\n@FIELD reg 18 5\n@FIELD reg 13 5\n@FIELD imm 0 23");
+        let add_format = format("x i");
+
+        let word = encoding.encode(0xA2, &add_format, &[Some(1), Some(-5)]);
+
+        assert_eq!(word, (0xA2 << 23) | (1 << 18) | 0x1FFB);
+        assert_eq!(encoding.decode_op_code(word), 0xA2);
+        assert_eq!(encoding.decode_operands(word, &add_format), vec![1, -5]);
+
+        // A wider op-code & a three-register format
+        let encoding = parse_encoding("@FIELD opcode 21 11\n@FIELD reg 16 5\n@FIELD reg 11 5\n@FIELD reg 6 5\n@FIELD imm 0 6");
+        let three_registers = format("x x x");
+        let word = encoding.encode(0x7FF, &three_registers, &[Some(1), Some(2), Some(3)]);
+
+        assert_eq!(encoding.decode_op_code(word), 0x7FF);
+        assert_eq!(encoding.decode_operands(word, &three_registers), vec![1, 2, 3]);
+    }
+}
@@ -1,11 +1,25 @@
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::string::ToString;
+use std::sync::OnceLock;
 use include_dir::{include_dir, Dir};
-use crate::instruction::micro_operation::{generate_empty_control_word, MICRO_OPERATIONS};
+use crate::instruction::micro_operation::{empty_control_word, machine_description, parse_micro_operations, use_micro_operations, MicroOperation, MICRO_OPERATIONS, MICRO_OPERATIONS_FILE_NAME};
+use crate::instruction::allocation::op_code_conflicts;
+use crate::instruction::encoding::ENCODING;
+use crate::instruction::operand::OperandKind;
+use crate::instruction::call_word::{PM_FLAG, STAGE_MASK, ZF_FLAG};
+use crate::instruction::validation::validate_instruction;
+use crate::util::code_error::{display_code_error, ErrorNotificationKind};
 use crate::util::exit::{exit, ExitCode };
 use crate::util::remove_comments::remove_comments_in_line;
 
 const INSTRUCTION_DIR: Dir = include_dir!("$CARGO_MANIFEST_DIR/instructions");
-const OUTPUT_MAP_STRING: [&str; 30] = ["PC_OUT", "PC_IN", "PC_INC", "MEM_ADDR_PTR_IN", "ALU_IN_A", "ALU_IN_B", "CAL_REG_A_IN", "CAL_REG_B_IN", "CAL_REG_A_OUT", "CAL_REG_B_OUT", "IMMEDIATE_OUT", "INSTR_IN", "MEM_OUT", "PLUS_OUT", "RESET_MICRO", "STDTRANS_IN", "STDTRANS_OUT", "STDTRANS_SEND", "ZF_IN", "ZF_OUT", "SUB_OUT", "MEM_BYTE_OUT", "LSH_OUT", "SP_OUT", "SP_IN", "MEM_IN", "SP_DEC_DW", "SP_INC_DW", "MEM_B_IN", "NAND_OUT"]; // The left-most string in the list will end up in the LSb of the control 'word'
+
+/// The largest op-code the CU can tell apart (the call word has 9 bits for it)
+const MAX_CU_OP_CODE: u16 = 0x01FF;
+
+/// The directory instructions are loaded from at runtime (if not set, the built-in ones are used)
+static INSTRUCTION_SET_DIR: OnceLock<PathBuf> = OnceLock::new();
 
 /*
 Personal Comment:
@@ -71,17 +85,17 @@ Personal Comment:
 #[derive(Debug)]
 pub struct Instruction {
     pub name: String,
-    pub format: Vec<bool>,              // A 0 is a register, a 1 an immediate value
-    pub op_code: u16,                   // This is only 8 bits rn, but the encoding would allow expanding to up to 9 bits
+    pub format: Vec<OperandKind>,       // The kinds of operands in order
+    pub op_code: u16,                   // As wide as the encoding's opcode field, but the CU only sees 9 bits
     pub stages: Vec<(u16, u64)>
 }
 
 impl Instruction {
-    pub fn new(name: String, format: Vec<bool>, stages: Vec<(u16, u64)>) -> Instruction {
+    pub fn new(name: String, format: Vec<OperandKind>, stages: Vec<(u16, u64)>) -> Instruction {
         Instruction { name, format, op_code: 0, stages }
     }
 
-    pub fn new_with_op(name: String, format: Vec<bool>, op_code: u16, stages: Vec<(u16, u64)>) -> Instruction {
+    pub fn new_with_op(name: String, format: Vec<OperandKind>, op_code: u16, stages: Vec<(u16, u64)>) -> Instruction {
         Instruction { name, format, op_code, stages }
     }
 
@@ -92,22 +106,51 @@ impl Instruction {
             return true;
         }
 
-        // Check whether any other argument other than the last one is an immediate value.
-        let mut format = self.format.clone();
-        if format.len() != 0{
-            format.remove(0);
-            for i in format.iter() {
-                if *i { return false /* nope */ }
-            }
+        // Check whether all operands fit into the encoding
+        if ENCODING.operand_shifts(&self.format).is_none() {
+            return true;
+        }
+
+        // Check whether the op-code fits into both the encoding and the CU's call word
+        if self.op_code > ENCODING.max_op_code() || self.op_code > MAX_CU_OP_CODE {
+            return true;
         }
 
         false /*success*/
     }
 
+    /// The flag combinations the instruction has versions for (without flags first).
+    pub fn flag_versions(&self) -> Vec<u16> {
+        let mut versions = vec![0];
+
+        for stage in self.stages.iter() {
+            let flags = stage.0 & (ZF_FLAG | PM_FLAG);
+
+            if !versions.contains(&flags) { versions.push(flags); }
+        }
+
+        versions.sort();
+        versions
+    }
+
+    /// The control word the CU outputs for a stage with the given flags set, using the same fallbacks as the instruction table (PM, then ZF, then both ignored).
+    pub fn resolve_stage(&self, stage: u16, flags: u16) -> Option<u64> {
+        [flags, flags & !PM_FLAG, flags & !ZF_FLAG, 0].iter()
+            .find_map(|&x| self.stages.iter().find(|y| y.0 & STAGE_MASK == stage && y.0 & (ZF_FLAG | PM_FLAG) == x))
+            .map(|x| x.1)
+    }
+
+    /// The format as written in instruction files (e.g. "x i").
+    pub fn format_notation(&self) -> String {
+        self.format.iter().map(|x| x.notation()).collect::<Vec<String>>().join(" ")
+    }
+
     pub fn from_string(string: String) -> Instruction {
-        // List of all available micro operations
-        let output_map: Vec<String> = OUTPUT_MAP_STRING.clone().into_iter().map(|s| s.to_string()).collect();
+        Instruction::from_string_with(string, &MICRO_OPERATIONS)
+    }
 
+    /// Like `from_string`, but with other micro operations than the ones in use (e.g. another revision's).
+    pub fn from_string_with(string: String, micro_operations: &[MicroOperation]) -> Instruction {
         let mut lines = string.lines().collect::<Vec<&str>>();
 
         let mut name = remove_comments_in_line(lines.first().unwrap().to_string());
@@ -115,22 +158,14 @@ impl Instruction {
         lines.remove(0);
 
         let format_string = remove_comments_in_line(lines.first().unwrap().to_string()).split_whitespace().collect::<Vec<&str>>().iter().map(|s| s.to_string()).collect::<Vec<String>>();
-        let mut format_vec: Vec<bool> = Vec::new();
+        let mut format_vec: Vec<OperandKind> = Vec::new();
         lines.remove(0);
 
         for format_expression in format_string.iter() {
-            match (*format_expression).as_str() {
-                "x" => {
-                    format_vec.push(false);
-                }
-
-                "i" => {
-                    format_vec.push(true);
-                }
-
-                _ => {
-                    exit(format!("Unknown expression ({}).\nShould be x for register or i for immediate value.\nThis was found in the code for the {} instruction.", format_expression, name), ExitCode::Other);
-                }
+            if let Some(kind) = OperandKind::from_string(format_expression) {
+                format_vec.push(kind);
+            } else {
+                exit(format!("Unknown expression ({}).\nShould be x or x[min-max] for registers, i, u<bits> or s<bits> for immediate values, rel<bits> for relative addresses or page/pageoff.\nThis was found in the code for the {} instruction.", format_expression, name), ExitCode::Other);
             }
         }
 
@@ -152,12 +187,12 @@ impl Instruction {
         let mut result = Instruction::new(name, format_vec, Vec::new());
         result.op_code = op_code;
 
-        let mut call_word: u16 = (op_code & 0x01FF) << 5;
+        let mut call_word: u16 = (op_code & MAX_CU_OP_CODE) << 5;
 
         // The output of the control unit when it receives the call_word as an input.
         // The call word is actually a word, unlike the control word, which is actually
         // a quad word.
-        let mut current_stage_control_word: u64 = generate_empty_control_word();
+        let mut current_stage_control_word: u64 = empty_control_word(micro_operations);
 
         for line in lines {
             let line_with_whitespaces = remove_comments_in_line(line.to_string());
@@ -169,8 +204,8 @@ impl Instruction {
             match line {
                 "@STAGE" | "@VERSION" => {
                     result.stages.push((call_word, current_stage_control_word));
-                    current_stage_control_word = 0;
-                    let mut current_stage = call_word & 0x1F; // The last five
+                    current_stage_control_word = empty_control_word(micro_operations);
+                    let mut current_stage = call_word & STAGE_MASK; // The last five
                     if line == "@STAGE"{
                         current_stage += 1;
                     }
@@ -180,25 +215,22 @@ impl Instruction {
 
                 "@ZF" => {
                     // Enable the "Zero Flag" bit in the call_word
-                    call_word = call_word | 0x4000u16;
+                    call_word = call_word | ZF_FLAG;
                     continue;
                 }
 
                 "@PM" => {
                     // Just enable the "Privileged Mode Flag" bit in the call_word
-                    call_word = call_word | 0x8000u16;
+                    call_word = call_word | PM_FLAG;
                     continue;
                 }
 
                 _ => {
                     // Go through every word and find the one that fits and add that info to the control word
-                    for i in MICRO_OPERATIONS.iter(){
-                        let instruction_name = i.name.to_string();
-
-                        if instruction_name != line { continue; }
+                    for i in micro_operations.iter(){
+                        if i.name != line { continue; }
 
-                        let mask = 1u64 << i.pos_in_control_word;
-                        current_stage_control_word = current_stage_control_word ^ mask;
+                        current_stage_control_word = i.activate(current_stage_control_word);
                     }
                 }
             }
@@ -211,10 +243,10 @@ impl Instruction {
 }
 
 pub fn micro_operation_at(idx: usize) -> String {
-    if idx >= OUTPUT_MAP_STRING.len() {
-        return "OUT OF RANGE".to_string();
+    match MICRO_OPERATIONS.iter().find(|x| x.pos_in_control_word as usize == idx) {
+        Some(micro_operation) => micro_operation.name.clone(),
+        None => "OUT OF RANGE".to_string(),
     }
-    OUTPUT_MAP_STRING[idx].to_string()
 }
 
 
@@ -224,55 +256,166 @@ impl Clone for Instruction {
     }
 }
 
-pub fn get_all_instructions() -> Vec<Instruction> {
-    let files = INSTRUCTION_DIR.files();
-    let mut instructions: Vec<Instruction> = Vec::new();
+/// Load instructions (and micro operations if the directory has them) from a directory at runtime instead of using the built-in ones.
+/// This must happen before any instructions or micro operations are used.
+pub fn use_instruction_set_dir(path: PathBuf) {
+    if !path.is_dir() {
+        exit(format!("Instruction set directory '{}' doesn't exist.", path.display()), ExitCode::BadArgument);
+    }
 
-    for file in files.enumerate() {
-        let file_contents = file.to_owned().1.contents_utf8().unwrap();
+    if read_instruction_files(&path).is_empty() {
+        exit(format!("Instruction set directory '{}' doesn't contain any instructions (*.txt files).", path.display()), ExitCode::BadArgument);
+    }
 
-        let instruction = Instruction::from_string(file_contents.to_string());
+    let micro_operations_path = path.join(MICRO_OPERATIONS_FILE_NAME);
 
-        if instruction.check() {
-            exit(format!("Instruction named {} didn't pass instruction check.", instruction.name), ExitCode::Other);
+    if micro_operations_path.exists() {
+        match fs::read_to_string(&micro_operations_path) {
+            Ok(contents) => use_micro_operations(contents),
+            Err(_) => exit(format!("Couldn't read micro operations from '{}'.", micro_operations_path.display()), ExitCode::ReadWriteError),
         }
+    }
+
+    let _ = INSTRUCTION_SET_DIR.set(path);
+}
+
+/// Everything else (e.g. the micro operations) isn't an instruction.
+fn is_instruction_file(path: &Path) -> bool {
+    path.extension().is_some_and(|x| x == "txt")
+}
 
+/// The names & contents of all instruction files, either from the instruction set directory or the built-in ones.
+fn get_instruction_files() -> Vec<(String, String)> {
+    let Some(dir) = INSTRUCTION_SET_DIR.get() else {
+        return INSTRUCTION_DIR.files()
+            .filter(|x| is_instruction_file(x.path()))
+            .map(|x| (format!("instructions/{}", x.path().display()), x.contents_utf8().unwrap().to_string()))
+            .collect();
+    };
 
-        // Don't ask why
-        instructions.push(Instruction {
-            name: instruction.name.clone(),
-            format: instruction.format.clone(),
-            op_code: instruction.op_code.clone(),
-            stages: instruction.stages.clone(),
-        });
+    read_instruction_files(dir)
+}
+
+/// The names & contents of all instruction files in a directory.
+fn read_instruction_files(dir: &Path) -> Vec<(String, String)> {
+    let entries = fs::read_dir(dir);
+
+    if entries.is_err() {
+        exit(format!("Couldn't read instruction set directory '{}'.", dir.display()), ExitCode::ReadWriteError);
     }
 
-    // Check whether there are instructions with the same op codes.
+    let mut paths = entries.unwrap().filter_map(|x| x.ok()).map(|x| x.path()).filter(|x| is_instruction_file(x)).collect::<Vec<PathBuf>>();
+    paths.sort();
 
-    let instruction_op_codes = instructions.iter().map(|i| i.op_code.clone()).collect::<Vec<u16>>();
+    paths.iter().map(|path| {
+        let contents = fs::read_to_string(path);
 
-    instructions.iter().for_each(| x | {
-        let x = x.clone();
+        if contents.is_err() {
+            exit(format!("Couldn't read instruction file '{}'.", path.display()), ExitCode::ReadWriteError);
+        }
+
+        (path.display().to_string(), contents.unwrap())
+    }).collect()
+}
+
+/// Load another instruction set (e.g. an older revision) without replacing the one in use.
+/// Uses the directory's micro operations if it has them, the ones in use otherwise. Returns None if there are no instructions in the directory.
+pub fn load_instruction_set(dir: &Path) -> Option<(Vec<Instruction>, Vec<MicroOperation>)> {
+    let files = read_instruction_files(dir);
+
+    if files.is_empty() { return None; }
+
+    let micro_operations_path = dir.join(MICRO_OPERATIONS_FILE_NAME);
+
+    let micro_operations = match fs::read_to_string(&micro_operations_path) {
+        Ok(contents) => parse_micro_operations(&contents),
+        Err(_) if !micro_operations_path.exists() => parse_micro_operations(machine_description()),
+        Err(_) => { exit(format!("Couldn't read micro operations from '{}'.", micro_operations_path.display()), ExitCode::ReadWriteError); Vec::new() }
+    };
+
+    let instructions = files.into_iter().map(|x| Instruction::from_string_with(x.1, &micro_operations)).collect();
+
+    Some((instructions, micro_operations))
+}
+
+/// The largest op-code an instruction can have (limited by both the encoding & the CU's call word).
+pub fn max_op_code() -> u16 {
+    ENCODING.max_op_code().min(MAX_CU_OP_CODE)
+}
+
+/// Validate an instruction file and print all problems, returns whether it's free of problems.
+fn validate_and_report(source: &str, file_name: String) -> bool {
+    let problems = validate_instruction(source);
+    let code = source.lines().map(|x| x.to_string()).collect::<Vec<String>>();
+
+    for problem in problems.iter() {
+        display_code_error(ErrorNotificationKind::Error, problem.line as i32, None, None, problem.title.clone(), problem.message.clone(), code.clone(), file_name.clone());
+    }
+
+    problems.is_empty()
+}
+
+/// All instructions with the files they're defined in (op-codes aren't checked for conflicts).
+pub fn get_instruction_definitions() -> Vec<(String, Instruction)> {
+    let files = get_instruction_files();
+    let mut definitions: Vec<(String, Instruction)> = Vec::new();
+    let mut invalid_files_count = 0;
+
+    for (file_name, file_contents) in files.iter() {
+        if !validate_and_report(file_contents, file_name.clone()) {
+            invalid_files_count += 1;
+            continue;
+        }
 
-        // The amount of instructions with the same OP-Code (including the instruction itself)
-        let op_code_frequency = instruction_op_codes.iter().filter(|&y| *y == x.op_code).count();
+        let instruction = Instruction::from_string(file_contents.to_string());
 
-        if op_code_frequency != 1 {
-            exit(format!("OP-Codes should be unique to each instruction, but there are {} more instructions using the same op-codes as the \"{}\" instruction.", op_code_frequency - 1, x.name), ExitCode::Other);
+        if instruction.check() {
+            exit(format!("Instruction named {} didn't pass instruction check.\nInstructions take at most 3 operands, which must fit the encoding's fields, and the op-code can't be larger than 0x{:X}.", instruction.name, max_op_code()), ExitCode::Other);
         }
-    });
 
-    instructions
+        definitions.push((file_name.clone(), instruction));
+    }
+
+    if invalid_files_count != 0 {
+        exit(format!("{} instruction file(s) contain invalid microcode.", invalid_files_count), ExitCode::Other);
+    }
+
+    definitions
+}
+
+pub fn get_all_instructions() -> Vec<Instruction> {
+    let definitions = get_instruction_definitions();
+
+    // Check whether there are instructions with the same op codes.
+    let conflicts = op_code_conflicts(&definitions);
+
+    if !conflicts.is_empty() {
+        exit(format!("OP-Codes should be unique to each instruction.\n{}\nRun smiscasm --opcode-map to see which op-codes are free.", conflicts.join("\n")), ExitCode::Other);
+    }
+
+    definitions.into_iter().map(|x| x.1).collect()
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::instruction::instruction::get_all_instructions;
+    use crate::instruction::call_word::{PM_FLAG, ZF_FLAG};
+    use crate::instruction::instruction::{get_all_instructions, Instruction};
 
     #[test]
     fn test_get_all_instructions() {
         // Just look if it fails
         let _ = get_all_instructions();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_versions() {
+        let instruction = Instruction::from_string("a\nx i\n01\n@STAGE\nPC_OUT\n@STAGE\nRESET_MICRO\n@VERSION\n@ZF\nPC_IN\nRESET_MICRO".to_string());
+
+        assert_eq!(instruction.format_notation(), "x i");
+        assert_eq!(instruction.flag_versions(), vec![0, ZF_FLAG]);
+        assert_eq!(instruction.resolve_stage(1, ZF_FLAG | PM_FLAG), instruction.resolve_stage(1, 0));
+        assert_ne!(instruction.resolve_stage(2, ZF_FLAG | PM_FLAG), instruction.resolve_stage(2, 0));
+        assert_eq!(instruction.resolve_stage(3, 0), None);
+    }
+}
@@ -1,58 +1,167 @@
+use std::sync::{LazyLock, OnceLock};
+use crate::util::exit::{exit, ExitCode};
+use crate::util::remove_comments::remove_comments_in_line;
+
+/// The machine description's file name, lives next to the instructions
+pub const MICRO_OPERATIONS_FILE_NAME: &str = "micro_operations.mops";
+
+const MICRO_OPERATIONS_STRING: &str = include_str!("../../instructions/micro_operations.mops");
+
+/// A machine description loaded at runtime, replaces the built-in one
+static MICRO_OPERATIONS_OVERRIDE: OnceLock<String> = OnceLock::new();
+
+pub static MICRO_OPERATIONS: LazyLock<Vec<MicroOperation>> = LazyLock::new(|| parse_micro_operations(machine_description()));
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct MicroOperation {
     /// The offset of the "device" in the control word (left shift, in bits).
     pub pos_in_control_word: u8,
-    
+
     /// The name expected in a file from instructions
-    pub name: &'static str,
-    
+    pub name: String,
+
     /// The default state
     pub is_active_low: bool,
+
+    /// What the micro operation does with the main bus
+    pub role: BusRole,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BusRole {
+    Driver,     // Puts a value onto the bus (only one at a time)
+    Reader,     // Takes the value from the bus
+    Control,    // Doesn't touch the bus
 }
 
+impl MicroOperation {
+    /// Sets the micro operation's line in the control word to its active state.
+    pub fn activate(&self, control_word: u64) -> u64 {
+        let mask = 1u64 << self.pos_in_control_word;
+
+        if self.is_active_low { control_word & !mask } else { control_word | mask }
+    }
+
+    /// Whether the micro operation's line is in its active state in the control word.
+    pub fn is_active(&self, control_word: u64) -> bool {
+        self.activate(control_word) == control_word
+    }
+}
+
+/// Use another machine description instead of the built-in one. Must be called before MICRO_OPERATIONS is used.
+pub fn use_micro_operations(string: String) {
+    let _ = MICRO_OPERATIONS_OVERRIDE.set(string);
+}
+
+/// The machine description in use (either loaded at runtime or the built-in one).
+pub fn machine_description() -> &'static str {
+    MICRO_OPERATIONS_OVERRIDE.get().map(|x| x.as_str()).unwrap_or(MICRO_OPERATIONS_STRING)
+}
+
+/// Reads micro operations from a machine description (one `NAME BIT high|low driver|reader|control` per line).
+pub fn parse_micro_operations(string: &str) -> Vec<MicroOperation> {
+    let mut micro_operations: Vec<MicroOperation> = Vec::new();
+
+    for line in string.lines() {
+        let line = remove_comments_in_line(line.to_string());
+        let words = line.split_whitespace().collect::<Vec<&str>>();
+
+        // Empty or describing the encoding
+        if words.is_empty() || words[0].starts_with('@') { continue; }
+
+        if words.len() != 4 {
+            exit(format!("Micro operation definition \"{}\" should consist of a name, a bit, high/low and a role.", line.trim()), ExitCode::Other);
+        }
+
+        let name = words[0].to_string();
+
+        let pos_in_control_word = words[1].parse::<u8>().ok().filter(|&x| x < 64);
+
+        if pos_in_control_word.is_none() {
+            exit(format!("Bit \"{}\" of micro operation {} isn't within the control word (0...63).", words[1], name), ExitCode::Other);
+        }
+
+        let is_active_low = match words[2] {
+            "high" => false,
+            "low" => true,
+            _ => { exit(format!("Micro operation {} should be either active high or low, not \"{}\".", name, words[2]), ExitCode::Other); false }
+        };
 
-pub const MICRO_OPERATIONS: [MicroOperation; 30] = [
-    MicroOperation { pos_in_control_word: 0, name: "PC_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 1, name: "PC_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 2, name: "PC_INC", is_active_low: false },
-    MicroOperation { pos_in_control_word: 3, name: "MEM_ADDR_PTR_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 4, name: "ALU_IN_A", is_active_low: false },
-    MicroOperation { pos_in_control_word: 5, name: "ALU_IN_B", is_active_low: false },
-    MicroOperation { pos_in_control_word: 6, name: "CAL_REG_A_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 7, name: "CAL_REG_B_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 8, name: "CAL_REG_A_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 9, name: "CAL_REG_B_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 10, name: "IMMEDIATE_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 11, name: "INSTR_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 12, name: "MEM_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 13, name: "PLUS_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 14, name: "RESET_MICRO", is_active_low: false },
-    MicroOperation { pos_in_control_word: 15, name: "STDTRANS_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 16, name: "STDTRANS_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 17, name: "STDTRANS_SEND", is_active_low: false },
-    MicroOperation { pos_in_control_word: 18, name: "ZF_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 19, name: "ZF_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 20, name: "SUB_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 21, name: "MEM_BYTE_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 22, name: "LSH_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 23, name: "SP_OUT", is_active_low: false },
-    MicroOperation { pos_in_control_word: 24, name: "SP_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 25, name: "MEM_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 26, name: "SP_DEC_DW", is_active_low: false },
-    MicroOperation { pos_in_control_word: 27, name: "SP_INC_DW", is_active_low: false },
-    MicroOperation { pos_in_control_word: 28, name: "MEM_B_IN", is_active_low: false },
-    MicroOperation { pos_in_control_word: 29, name: "NAND_OUT", is_active_low: false },
-];
+        let role = match words[3] {
+            "driver" => BusRole::Driver,
+            "reader" => BusRole::Reader,
+            "control" => BusRole::Control,
+            _ => { exit(format!("Unknown role \"{}\" for micro operation {}.\nShould be driver, reader or control.", words[3], name), ExitCode::Other); BusRole::Control }
+        };
+
+        let micro_operation = MicroOperation { pos_in_control_word: pos_in_control_word.unwrap(), name, is_active_low, role };
+
+        if let Some(other) = micro_operations.iter().find(|x| x.name == micro_operation.name || x.pos_in_control_word == micro_operation.pos_in_control_word) {
+            exit(format!("Micro operations {} and {} share a name or a bit.", other.name, micro_operation.name), ExitCode::Other);
+        }
+
+        micro_operations.push(micro_operation);
+    }
+
+    micro_operations
+}
 
 /// Generates a default control "word" for when nothing is happening.
 pub fn generate_empty_control_word() -> u64 {
+    empty_control_word(&MICRO_OPERATIONS)
+}
+
+/// The control word with all of the micro operations inactive.
+pub fn empty_control_word(micro_operations: &[MicroOperation]) -> u64 {
     let mut control_word: u64 = 0;
-    
-    for micro_operation in MICRO_OPERATIONS.iter() {
+
+    for micro_operation in micro_operations.iter() {
         let offset = micro_operation.pos_in_control_word;
         let bit = micro_operation.is_active_low;
-        
+
         control_word |= u64::from(bit) << offset;
     }
-    
+
     control_word
-}
\ No newline at end of file
+}
+
+/// The micro operations a control word activates (in the order they're defined in).
+pub fn active_micro_operations(control_word: u64) -> Vec<&'static MicroOperation> {
+    MICRO_OPERATIONS.iter().filter(|x| x.is_active(control_word)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::micro_operation::{active_micro_operations, generate_empty_control_word, parse_micro_operations, BusRole, MicroOperation, MICRO_OPERATIONS};
+
+    #[test]
+    fn test_parse_micro_operations() {
+        let micro_operations = parse_micro_operations("# NAME BIT ACTIVE ROLE\nPC_OUT 0 high driver\n\nHALT   63 low control # comment\n@FIELD opcode 23 9");
+
+        assert_eq!(micro_operations, vec![
+            MicroOperation { pos_in_control_word: 0, name: "PC_OUT".to_string(), is_active_low: false, role: BusRole::Driver },
+            MicroOperation { pos_in_control_word: 63, name: "HALT".to_string(), is_active_low: true, role: BusRole::Control },
+        ]);
+    }
+
+    #[test]
+    fn test_activate() {
+        let active_high = MicroOperation { pos_in_control_word: 1, name: "A".to_string(), is_active_low: false, role: BusRole::Control };
+        let active_low = MicroOperation { pos_in_control_word: 2, name: "B".to_string(), is_active_low: true, role: BusRole::Control };
+
+        // Activating twice mustn't toggle it back
+        assert_eq!(active_high.activate(active_high.activate(0b100)), 0b110);
+        assert_eq!(active_low.activate(active_low.activate(0b100)), 0b000);
+    }
+
+    #[test]
+    fn test_active_micro_operations() {
+        assert!(active_micro_operations(generate_empty_control_word()).is_empty());
+
+        let control_word = MICRO_OPERATIONS.iter().filter(|x| x.name == "PC_OUT" || x.name == "RESET_MICRO").fold(generate_empty_control_word(), |word, x| x.activate(word));
+        let mut names = active_micro_operations(control_word).iter().map(|x| x.name.clone()).collect::<Vec<String>>();
+        names.sort();
+
+        assert_eq!(names, vec!["PC_OUT".to_string(), "RESET_MICRO".to_string()]);
+    }
+}
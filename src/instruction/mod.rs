@@ -1,2 +1,8 @@
+pub mod allocation;
+pub mod call_word;
+pub mod encoding;
 pub mod instruction;
-mod micro_operation;
\ No newline at end of file
+pub mod micro_operation;
+pub mod operand;
+pub mod table;
+pub(crate) mod validation;
\ No newline at end of file
@@ -0,0 +1,163 @@
+// The kinds of operands instructions can take (the second line of an instruction file).
+
+use std::fmt;
+use crate::config::MEMORY_PAGE_SIZE;
+
+/// The widest immediate value possible
+const MAX_IMMEDIATE_BITS: u8 = 31;
+
+/// The width of page operands (like `i`)
+const PAGE_BITS: u8 = 13;
+
+/// The width of a register operand
+pub const REGISTER_BITS: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OperandKind {
+    Register { min: u8, max: u8 },              // x (any register) or x[min-max] (a register class)
+    Immediate { bits: u8, signed: bool },       // i (13 bits, signed), u<bits> or s<bits>
+    Relative { bits: u8 },                      // rel<bits>, a signed offset to the next instruction (e.g. label@RELATIVE)
+    Page,                                       // page, a memory page (e.g. label@PAGE)
+    PageOffset,                                 // pageoff, an offset within a memory page (e.g. label@PAGEOFF)
+}
+
+impl OperandKind {
+    /// Parse an operand kind as written in instruction files.
+    pub fn from_string(string: &str) -> Option<OperandKind> {
+        match string {
+            "x" => return Some(OperandKind::Register { min: 0, max: 31 }),
+            "i" => return Some(OperandKind::Immediate { bits: 13, signed: true }),
+            "page" => return Some(OperandKind::Page),
+            "pageoff" => return Some(OperandKind::PageOffset),
+            _ => {}
+        }
+
+        // Register classes like x[0-23]
+        if let Some(range) = string.strip_prefix("x[").and_then(|x| x.strip_suffix(']')) {
+            let (min, max) = range.split_once('-')?;
+            let (min, max) = (min.parse::<u8>().ok()?, max.parse::<u8>().ok()?);
+
+            if min > max || max > 31 { return None; }
+
+            return Some(OperandKind::Register { min, max });
+        }
+
+        let parse_bits = |x: &str| x.parse::<u8>().ok().filter(|&x| x > 0 && x <= MAX_IMMEDIATE_BITS);
+
+        if let Some(bits) = string.strip_prefix("rel") { return Some(OperandKind::Relative { bits: parse_bits(bits)? }); }
+        if let Some(bits) = string.strip_prefix('u') { return Some(OperandKind::Immediate { bits: parse_bits(bits)?, signed: false }); }
+        if let Some(bits) = string.strip_prefix('s') { return Some(OperandKind::Immediate { bits: parse_bits(bits)?, signed: true }); }
+
+        None
+    }
+
+    /// The operand kind as written in instruction files (the opposite of `from_string`).
+    pub fn notation(&self) -> String {
+        match self {
+            OperandKind::Register { min: 0, max: 31 } => "x".to_string(),
+            OperandKind::Register { min, max } => format!("x[{}-{}]", min, max),
+            OperandKind::Immediate { bits: 13, signed: true } => "i".to_string(),
+            OperandKind::Immediate { bits, signed: true } => format!("s{}", bits),
+            OperandKind::Immediate { bits, signed: false } => format!("u{}", bits),
+            OperandKind::Relative { bits } => format!("rel{}", bits),
+            OperandKind::Page => "page".to_string(),
+            OperandKind::PageOffset => "pageoff".to_string(),
+        }
+    }
+
+    pub fn is_register(&self) -> bool {
+        matches!(self, OperandKind::Register { .. })
+    }
+
+    /// The amount of bits the operand takes up in the instruction.
+    pub fn bits(&self) -> u8 {
+        match self {
+            OperandKind::Register { .. } => REGISTER_BITS,
+            OperandKind::Immediate { bits, .. } | OperandKind::Relative { bits } => *bits,
+            OperandKind::Page => PAGE_BITS,
+            OperandKind::PageOffset => MEMORY_PAGE_SIZE.trailing_zeros() as u8,
+        }
+    }
+
+    /// The smallest & largest values the operand accepts.
+    pub fn range(&self) -> (i64, i64) {
+        let bits = self.bits() as u32;
+
+        match self {
+            OperandKind::Register { min, max } => (*min as i64, *max as i64),
+            OperandKind::Immediate { signed: true, .. } | OperandKind::Relative { .. } => (-(1i64 << (bits - 1)), (1i64 << (bits - 1)) - 1),
+            OperandKind::Immediate { signed: false, .. } | OperandKind::Page | OperandKind::PageOffset => (0, (1i64 << bits) - 1),
+        }
+    }
+
+    /// Whether the value fits into the operand.
+    pub fn accepts(&self, value: i64) -> bool {
+        let (min, max) = self.range();
+
+        value >= min && value <= max
+    }
+
+    /// The value as it's stored in the instruction (two's complement, cut down to the operand's width).
+    pub fn encode(&self, value: i64) -> u32 {
+        (value as u32) & ((1u32 << self.bits()) - 1)
+    }
+
+    /// The value from its stored form (the opposite of `encode`), bits above the operand's width are ignored.
+    pub fn decode(&self, raw: u32) -> i64 {
+        let value = (raw & ((1u32 << self.bits()) - 1)) as i64;
+        let (min, _) = self.range();
+
+        // Sign-extend signed kinds
+        if min < 0 && value >= 1 << (self.bits() - 1) { value - (1 << self.bits()) } else { value }
+    }
+}
+
+impl fmt::Display for OperandKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperandKind::Register { min: 0, max: 31 } => write!(f, "Register"),
+            OperandKind::Register { min, max } => write!(f, "Register x{}...x{}", min, max),
+            OperandKind::Immediate { bits, signed: true } => write!(f, "{}-bit Signed Imm. Value", bits),
+            OperandKind::Immediate { bits, signed: false } => write!(f, "{}-bit Unsigned Imm. Value", bits),
+            OperandKind::Relative { bits } => write!(f, "{}-bit Relative Address", bits),
+            OperandKind::Page => write!(f, "Page"),
+            OperandKind::PageOffset => write!(f, "Page Offset"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::operand::OperandKind;
+
+    #[test]
+    fn test_from_string() {
+        assert_eq!(OperandKind::from_string("x"), Some(OperandKind::Register { min: 0, max: 31 }));
+        assert_eq!(OperandKind::from_string("x[0-23]"), Some(OperandKind::Register { min: 0, max: 23 }));
+        assert_eq!(OperandKind::from_string("i"), Some(OperandKind::Immediate { bits: 13, signed: true }));
+        assert_eq!(OperandKind::from_string("u8"), Some(OperandKind::Immediate { bits: 8, signed: false }));
+        assert_eq!(OperandKind::from_string("rel13"), Some(OperandKind::Relative { bits: 13 }));
+        assert_eq!(OperandKind::from_string("pageoff"), Some(OperandKind::PageOffset));
+        assert_eq!(OperandKind::from_string("x[5-40]"), None);
+        assert_eq!(OperandKind::from_string("u32"), None);
+        assert_eq!(OperandKind::from_string("y"), None);
+
+        for notation in ["x", "x[0-23]", "i", "s8", "u4", "rel13", "page", "pageoff"] {
+            assert_eq!(OperandKind::from_string(notation).unwrap().notation(), notation);
+        }
+    }
+
+    #[test]
+    fn test_ranges() {
+        let i = OperandKind::from_string("i").unwrap();
+        assert_eq!(i.range(), (-4096, 4095));
+        assert_eq!(i.encode(-1), 0x1FFF);
+        assert_eq!(i.decode(0x1FFF), -1);
+        assert_eq!(OperandKind::from_string("u8").unwrap().decode(0x1FF), 0xFF);
+
+        assert!(OperandKind::PageOffset.accepts(4095));
+        assert!(!OperandKind::PageOffset.accepts(4096));
+        assert!(!OperandKind::from_string("u4").unwrap().accepts(-1));
+        assert!(!OperandKind::from_string("x[0-23]").unwrap().accepts(31));
+    }
+}
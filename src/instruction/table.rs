@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use crate::instruction::call_word::{flags_description, PM_FLAG, STAGE_MASK, ZF_FLAG};
+use crate::instruction::instruction::{load_instruction_set, Instruction};
+use crate::instruction::micro_operation::{empty_control_word, MicroOperation, MICRO_OPERATIONS};
+use crate::util::exit::{exit, ExitCode};
+
+/// The amount of call words the control unit can receive (and therefore the amount of control words in the table).
+pub const TABLE_SIZE: usize = 65536;
+
+/// The amount of EEPROMs the control word is split into (one byte each)
+pub const EEPROM_COUNT: usize = 8;
+
+/// The name of a generated table file, either for one EEPROM (1 is the MSB) or for the whole control word (None).
+pub fn table_file_name(eeprom: Option<usize>, suffix: &str) -> String {
+    match eeprom {
+        Some(1) => format!("smiscasm_instructions-1-MSB{}", suffix),
+        Some(EEPROM_COUNT) => format!("smiscasm_instructions-{}-LSB{}", EEPROM_COUNT, suffix),
+        Some(i) => format!("smiscasm_instructions-{}{}", i, suffix),
+        None => format!("smiscasm_instructions{}", suffix),
+    }
+}
+
+/// Generates all control words; position in vector counts as address/caller.
+/// Call words no instruction uses get the empty control word (of the micro operations the instructions were read with).
+pub fn generate_control_words(instructions: Vec<Instruction>, empty_control_word: u64) -> Vec<u64> {
+    // Every stage's control word by its call word.
+    // If multiple stages share a call word, the first one found wins.
+    let mut index: HashMap<u16, u64> = HashMap::new();
+
+    for instruction in instructions.iter() {
+        for stage in instruction.stages.iter() {
+            index.entry(stage.0).or_insert(stage.1);
+        }
+    }
+
+    (0..TABLE_SIZE).map(|i| {
+        let i = i as u16;
+
+        // Look for the best match, then look if there are versions without the Z/PM flags set.
+        // If i is above 32768, the MSb refers to the PM (privileged mode) flag.
+        // Some instructions only have one (privilege-independent) version tho.
+        // If i is above 16384, the second MSb refers to the ZF. Most instructions are ZF-independent.
+        // Some instructions (if not most of them still) are both ZF and PM flag independent.
+        // If nothing matches, push the empty control word (active-low lines aren't asserted).
+        [i, i & 0x7FFF, i & 0xBFFF, i & 0x3FFF].iter()
+            .find_map(|call_word| index.get(call_word))
+            .copied()
+            .unwrap_or(empty_control_word)
+    }).collect()
+}
+
+/// Splits the control words into one byte-wide table per EEPROM.
+/// Table one represents the MSB (byte not bit) while table eight represents the LSB.
+pub fn split_control_words(control_words: &[u64]) -> [Vec<u8>; 8] {
+    let mut control_words_u8: [Vec<u8>; 8] = Default::default();
+
+    for control_word in control_words.iter() {
+        for (i, table) in control_words_u8.iter_mut().enumerate() {
+            table.push((control_word >> (56 - i * 8)) as u8);
+        }
+    }
+
+    control_words_u8
+}
+
+/// Read the control words from a directory: either an instruction set (*.txt files) or previously generated raw tables
+/// (the combined one or all eight). Returns the micro operations to decode them with, too.
+pub fn read_control_words(dir: &Path) -> (Vec<u64>, Vec<MicroOperation>) {
+    if !dir.is_dir() {
+        exit(format!("'{}' isn't a directory.", dir.display()), ExitCode::BadArgument);
+    }
+
+    if let Some((instructions, micro_operations)) = load_instruction_set(dir) {
+        return (generate_control_words(instructions, empty_control_word(&micro_operations)), micro_operations);
+    }
+
+    let read_table = |name: String, width: usize| -> Option<Vec<u8>> {
+        let contents = fs::read(dir.join(&name)).ok()?;
+
+        if contents.len() != TABLE_SIZE * width {
+            exit(format!("'{}' should be {} bytes long, but is {}.", dir.join(&name).display(), TABLE_SIZE * width, contents.len()), ExitCode::BadArgument);
+        }
+
+        Some(contents)
+    };
+
+    if let Some(contents) = read_table(table_file_name(None, ".o"), EEPROM_COUNT) {
+        let control_words = contents.chunks(EEPROM_COUNT).map(|x| u64::from_be_bytes(x.try_into().unwrap())).collect();
+        return (control_words, MICRO_OPERATIONS.clone());
+    }
+
+    let tables = (1..=EEPROM_COUNT).map(|i| read_table(table_file_name(Some(i), ".o"), 1)).collect::<Option<Vec<Vec<u8>>>>();
+
+    let Some(tables) = tables else {
+        exit(format!("'{}' contains neither instructions nor raw instruction tables (generated with --generate-instruction-table).", dir.display()), ExitCode::BadArgument);
+        return (Vec::new(), Vec::new());
+    };
+
+    let control_words = (0..TABLE_SIZE).map(|i| tables.iter().fold(0u64, |word, table| (word << 8) | table[i] as u64)).collect();
+
+    (control_words, MICRO_OPERATIONS.clone())
+}
+
+/// The call words whose control words differ.
+pub fn diff_control_words(old: &[u64], new: &[u64]) -> Vec<u16> {
+    (0..TABLE_SIZE).filter(|&i| old.get(i) != new.get(i)).map(|i| i as u16).collect()
+}
+
+/// The EEPROMs (1 is the MSB) whose byte differs between the control words.
+pub fn changed_eeproms(old: u64, new: u64) -> Vec<usize> {
+    (1..=EEPROM_COUNT).filter(|i| (old ^ new) >> ((EEPROM_COUNT - i) * 8) & 0xFF != 0).collect()
+}
+
+/// Which call words changed (decoded into op-code, stage & flags, with the micro operations turned on/off) and which EEPROMs need reflashing.
+pub fn diff_report(old: &(Vec<u64>, Vec<MicroOperation>), new: &(Vec<u64>, Vec<MicroOperation>)) -> String {
+    let changed = diff_control_words(&old.0, &new.0);
+
+    if changed.is_empty() {
+        return "The instruction tables are identical, no EEPROM needs reflashing.\n".to_string();
+    }
+
+    let mut changed_bytes = [0usize; EEPROM_COUNT];
+    let mut details = String::new();
+
+    for &call_word in changed.iter() {
+        let (old_word, new_word) = (old.0[call_word as usize], new.0[call_word as usize]);
+        let eeproms = changed_eeproms(old_word, new_word);
+
+        eeproms.iter().for_each(|&x| changed_bytes[x - 1] += 1);
+
+        let active = |word: u64, micro_operations: &[MicroOperation]| micro_operations.iter().filter(|x| x.is_active(word)).map(|x| x.name.clone()).collect::<Vec<String>>();
+        let (old_names, new_names) = (active(old_word, &old.1), active(new_word, &new.1));
+
+        let removed = old_names.iter().filter(|x| !new_names.contains(x)).cloned().collect::<Vec<String>>();
+        let added = new_names.iter().filter(|x| !old_names.contains(x)).cloned().collect::<Vec<String>>();
+
+        details.push_str(&format!(
+            "0x{:04X}  op-code 0x{:02X}, stage {}, {}  (0x{:016X} -> 0x{:016X}, EEPROM {})\n",
+            call_word, (call_word >> 5) & 0x1FF, call_word & STAGE_MASK, flags_description(call_word & (ZF_FLAG | PM_FLAG)), old_word, new_word,
+            eeproms.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "),
+        ));
+
+        if !removed.is_empty() { details.push_str(&format!("  - {}\n", removed.join(", "))); }
+        if !added.is_empty() { details.push_str(&format!("  + {}\n", added.join(", "))); }
+    }
+
+    let to_reflash = (1..=EEPROM_COUNT).filter(|i| changed_bytes[i - 1] != 0).collect::<Vec<usize>>();
+
+    let mut result = format!("{} call word(s) changed, reflash EEPROM {} (1 is the MSB).\n", changed.len(), to_reflash.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "));
+
+    for i in to_reflash.iter() {
+        result.push_str(&format!("  EEPROM {} ({}): {} byte(s) changed\n", i, table_file_name(Some(*i), ".o"), changed_bytes[i - 1]));
+    }
+
+    result.push('\n');
+    result.push_str(&details);
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+    use crate::instruction::instruction::{get_all_instructions, Instruction};
+    use crate::instruction::micro_operation::{empty_control_word, generate_empty_control_word, parse_micro_operations, MICRO_OPERATIONS};
+    use crate::instruction::table::{changed_eeproms, diff_control_words, diff_report, generate_control_words, split_control_words, table_file_name, TABLE_SIZE};
+
+    #[test]
+    fn test_generate_control_words() {
+        let instructions = vec![Instruction::new_with_op("test".to_string(), vec![], 1, vec![(0x0020, 0x1), (0x4020, 0x2), (0x0021, 0x3)])];
+
+        let control_words = generate_control_words(instructions, 0x0);
+
+        assert_eq!(control_words.len(), TABLE_SIZE);
+        assert_eq!(control_words[0x0020], 0x1);
+        assert_eq!(control_words[0x4020], 0x2);     // ZF version
+        assert_eq!(control_words[0xC020], 0x2);     // ZF version, PM-independent
+        assert_eq!(control_words[0x8021], 0x3);     // Flag-independent
+        assert_eq!(control_words[0x0022], 0x0);     // Unused
+    }
+
+    #[test]
+    fn test_generate_control_words_first_match_wins() {
+        let instructions = vec![
+            Instruction::new_with_op("a".to_string(), vec![], 1, vec![(0x0020, 0x1)]),
+            Instruction::new_with_op("b".to_string(), vec![], 1, vec![(0x0020, 0x2), (0x4020, 0x3)]),
+        ];
+
+        let control_words = generate_control_words(instructions, 0x0);
+
+        assert_eq!(control_words[0x0020], 0x1);
+        assert_eq!(control_words[0x8020], 0x1);
+        assert_eq!(control_words[0x4020], 0x3);
+    }
+
+    #[test]
+    fn test_generate_control_words_active_low() {
+        // Unused call words must not assert active-low lines
+        let micro_operations = parse_micro_operations("PC_OUT 0 high driver\nHALT 1 low control\nRESET_MICRO 2 low control\n");
+        let empty = empty_control_word(&micro_operations);
+        let instructions = vec![Instruction::new_with_op("test".to_string(), vec![], 1, vec![(0x0020, 0x5)])];
+
+        let control_words = generate_control_words(instructions, empty);
+
+        assert_eq!(empty, 0x6);
+        assert_eq!(control_words[0x0020], 0x5);
+        assert_eq!(control_words[0x0022], 0x6);
+        assert_eq!(control_words[0xC022], 0x6);
+    }
+
+    #[test]
+    fn test_generate_control_words_unchanged() {
+        // The 8 tables for the current instructions as generated before the index was introduced
+        // (plus the RESET_MICRO fixed in adrp_i.txt's last stage)
+        let tables = split_control_words(&generate_control_words(get_all_instructions(), generate_empty_control_word()));
+        let hash = Sha256::digest(tables.concat()).iter().map(|x| format!("{:02x}", x)).collect::<String>();
+
+        assert_eq!(hash, "181e68d340ac40fa783614a247f1e5c3f6cd3af8954ebc5533d6e9b4031a55c6");
+    }
+
+    #[test]
+    fn test_split_control_words() {
+        let tables = split_control_words(&[0x0102030405060708, 0xFF]);
+
+        assert_eq!(tables[0], vec![0x01, 0x00]);
+        assert_eq!(tables[3], vec![0x04, 0x00]);
+        assert_eq!(tables[7], vec![0x08, 0xFF]);
+    }
+
+    #[test]
+    fn test_table_file_name() {
+        assert_eq!(table_file_name(Some(1), ".o"), "smiscasm_instructions-1-MSB.o");
+        assert_eq!(table_file_name(Some(4), ".hex"), "smiscasm_instructions-4.hex");
+        assert_eq!(table_file_name(Some(8), ".o"), "smiscasm_instructions-8-LSB.o");
+        assert_eq!(table_file_name(None, ".mem"), "smiscasm_instructions.mem");
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = generate_control_words(get_all_instructions(), generate_empty_control_word());
+        let mut new = old.clone();
+
+        assert!(diff_control_words(&old, &new).is_empty());
+
+        // Turn PC_OUT off & PC_IN on in stage 1 of op-code 0xA0, without flags
+        let pc_out = MICRO_OPERATIONS.iter().find(|x| x.name == "PC_OUT").unwrap();
+        let pc_in = MICRO_OPERATIONS.iter().find(|x| x.name == "PC_IN").unwrap();
+        let call_word = (0xA0 << 5) | 1;
+
+        new[call_word] = pc_in.activate(new[call_word] ^ (1 << pc_out.pos_in_control_word));
+
+        assert_eq!(diff_control_words(&old, &new), vec![call_word as u16]);
+        assert_eq!(changed_eeproms(old[call_word], new[call_word]), vec![8]);
+
+        let report = diff_report(&(old, MICRO_OPERATIONS.clone()), &(new, MICRO_OPERATIONS.clone()));
+
+        assert!(report.starts_with("1 call word(s) changed, reflash EEPROM 8 (1 is the MSB).\n"));
+        assert!(report.contains("0x1401  op-code 0xA0, stage 1, no flags"));
+        assert!(report.contains("  - PC_OUT\n  + PC_IN\n"));
+    }
+}
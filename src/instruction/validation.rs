@@ -0,0 +1,189 @@
+// Checks instruction definitions for microcode that can't work on the actual hardware.
+// It's also run by the build script, so it only uses the call word, the micro operations & the comment removal.
+
+use crate::instruction::call_word::{flags_description, PM_FLAG, STAGE_MASK, ZF_FLAG};
+use crate::instruction::micro_operation::{BusRole, MICRO_OPERATIONS};
+use crate::util::remove_comments::remove_comments_in_line;
+
+/// The amount of stages an instruction can have (the stage counter is 5 bits wide)
+pub const MAX_STAGES: u16 = STAGE_MASK + 1;
+
+/// A mistake in an instruction file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MicrocodeProblem {
+    pub line: usize,        // Starting at 0
+    pub title: String,
+    pub message: String,
+}
+
+/// A stage as written in the instruction file
+struct SourceStage {
+    stage: u16,
+    flags: u16,
+    line: usize,                                // The line of the @STAGE/@VERSION that started the stage
+    micro_operations: Vec<(String, usize)>,     // Name & line
+}
+
+impl SourceStage {
+    fn contains(&self, name: &str) -> bool {
+        self.micro_operations.iter().any(|x| x.0 == name)
+    }
+}
+
+/// Check an instruction file's microcode, returns every problem found.
+pub fn validate_instruction(source: &str) -> Vec<MicrocodeProblem> {
+    let mut problems: Vec<MicrocodeProblem> = Vec::new();
+    let stages = read_stages(source, &mut problems);
+
+    check_buses(&stages, &mut problems);
+    check_versions(&stages, &mut problems);
+    check_reset_paths(&stages, &mut problems);
+
+    problems.sort_by_key(|x| x.line);
+    problems
+}
+
+/// Split the file into stages the same way `Instruction::from_string` does, but keep the lines.
+fn read_stages(source: &str, problems: &mut Vec<MicrocodeProblem>) -> Vec<SourceStage> {
+    let mut stages: Vec<SourceStage> = Vec::new();
+    let mut current = SourceStage { stage: 0, flags: 0, line: 2, micro_operations: Vec::new() }; // Stage 0 starts right after the op-code
+
+    // The first three lines are the name, the format & the op-code.
+    for (line_number, line) in source.lines().enumerate().skip(3) {
+        let line_without_comments = remove_comments_in_line(line.to_string());
+        let Some(word) = line_without_comments.split_whitespace().next() else { continue; };
+
+        match word {
+            "@STAGE" | "@VERSION" => {
+                let stage = if word == "@STAGE" { current.stage + 1 } else { current.stage };
+
+                if stage == MAX_STAGES && current.stage < MAX_STAGES {
+                    problems.push(MicrocodeProblem { line: line_number, title: "Too many stages".to_string(), message: format!("Instructions can't have more than {} stages.", MAX_STAGES) });
+                }
+
+                stages.push(current);
+                current = SourceStage { stage, flags: 0, line: line_number, micro_operations: Vec::new() };
+            }
+
+            "@ZF" => current.flags |= ZF_FLAG,
+
+            "@PM" => current.flags |= PM_FLAG,
+
+            _ => {
+                if !MICRO_OPERATIONS.iter().any(|x| x.name == word) {
+                    problems.push(MicrocodeProblem { line: line_number, title: "Unknown micro operation".to_string(), message: format!("There is no micro operation named \"{}\".", word) });
+                    continue;
+                }
+
+                current.micro_operations.push((word.to_string(), line_number));
+            }
+        }
+    }
+
+    stages.push(current);
+
+    stages
+}
+
+/// Only one micro operation may drive the bus per stage, and reading it only makes sense if someone does.
+fn check_buses(stages: &[SourceStage], problems: &mut Vec<MicrocodeProblem>) {
+    for stage in stages.iter() {
+        let with_role = |role: BusRole| stage.micro_operations.iter()
+            .filter(|x| MICRO_OPERATIONS.iter().any(|y| y.name == x.0 && y.role == role))
+            .collect::<Vec<&(String, usize)>>();
+
+        let drivers = with_role(BusRole::Driver);
+        let readers = with_role(BusRole::Reader);
+
+        if drivers.len() > 1 {
+            let names = drivers.iter().map(|x| x.0.clone()).collect::<Vec<String>>().join(", ");
+            problems.push(MicrocodeProblem { line: drivers[1].1, title: "Bus conflict".to_string(), message: format!("Stage {} drives the bus from multiple sources at once ({}).", stage.stage, names) });
+        }
+
+        if drivers.is_empty() {
+            for reader in readers.iter() {
+                problems.push(MicrocodeProblem { line: reader.1, title: "Reading an undriven bus".to_string(), message: format!("{} reads the bus in stage {}, but nothing drives it.", reader.0, stage.stage) });
+            }
+        }
+    }
+}
+
+/// Every stage & flag combination may only be defined once.
+fn check_versions(stages: &[SourceStage], problems: &mut Vec<MicrocodeProblem>) {
+    for (i, stage) in stages.iter().enumerate() {
+        if stages[..i].iter().any(|x| x.stage == stage.stage && x.flags == stage.flags) {
+            problems.push(MicrocodeProblem { line: stage.line, title: "Duplicate version".to_string(), message: format!("Stage {} has already been defined for {}.", stage.stage, flags_description(stage.flags)) });
+        }
+    }
+}
+
+/// Every stage must eventually lead to a RESET_MICRO, no matter which flags are set.
+fn check_reset_paths(stages: &[SourceStage], problems: &mut Vec<MicrocodeProblem>) {
+    let mut reported_lines: Vec<usize> = Vec::new();
+
+    for flags in [0, ZF_FLAG, PM_FLAG, ZF_FLAG | PM_FLAG] {
+        for start in 0..MAX_STAGES {
+            let Some(mut current) = resolve(stages, start, flags) else { continue; };
+
+            while !current.contains("RESET_MICRO") {
+                match resolve(stages, current.stage + 1, flags) {
+                    Some(next) if current.stage + 1 < MAX_STAGES => current = next,
+                    _ => {
+                        if !reported_lines.contains(&current.line) {
+                            reported_lines.push(current.line);
+                            problems.push(MicrocodeProblem { line: current.line, title: "No RESET_MICRO path".to_string(), message: format!("Stage {} never reaches a RESET_MICRO for {} as there is no stage after it.", current.stage, flags_description(flags)) });
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Find the stage the CU would execute, using the same fallbacks as the instruction table (PM, then ZF, then both ignored).
+fn resolve(stages: &[SourceStage], stage: u16, flags: u16) -> Option<&SourceStage> {
+    [flags, flags & !PM_FLAG, flags & !ZF_FLAG, 0].iter()
+        .find_map(|&x| stages.iter().find(|y| y.stage == stage && y.flags == x))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::validation::validate_instruction;
+
+    fn titles(source: &str) -> Vec<(usize, String)> {
+        validate_instruction(source).iter().map(|x| (x.line, x.title.clone())).collect()
+    }
+
+    #[test]
+    fn test_valid_instruction() {
+        assert_eq!(titles("jmp\nx i\nD2\n\n@STAGE\nPC_OUT\nMEM_ADDR_PTR_IN\n@STAGE\nCAL_REG_A_OUT\nZF_IN\n@STAGE\nRESET_MICRO\n@VERSION\n@ZF\nIMMEDIATE_OUT\nPC_IN\nRESET_MICRO"), vec![]);
+    }
+
+    #[test]
+    fn test_unknown_micro_operation() {
+        assert_eq!(titles("a\n\n01\n@STAGE\nPC_OUTT # typo\nRESET_MICRO"), vec![(4, "Unknown micro operation".to_string())]);
+    }
+
+    #[test]
+    fn test_bus_conflicts() {
+        assert_eq!(titles("a\n\n01\n@STAGE\nPC_OUT\nMEM_OUT\nINSTR_IN\nRESET_MICRO"), vec![(5, "Bus conflict".to_string())]);
+        assert_eq!(titles("a\n\n01\n@STAGE\nALU_IN_A\nRESET_MICRO"), vec![(4, "Reading an undriven bus".to_string())]);
+    }
+
+    #[test]
+    fn test_stages() {
+        // No reset after the last stage
+        assert_eq!(titles("a\n\n01\n@STAGE\nPC_OUT\nMEM_ADDR_PTR_IN"), vec![(3, "No RESET_MICRO path".to_string())]);
+
+        // Only the ZF version resets
+        assert_eq!(titles("a\n\n01\n@STAGE\n@VERSION\n@ZF\nRESET_MICRO"), vec![(3, "No RESET_MICRO path".to_string())]);
+
+        // The same version twice
+        assert_eq!(titles("a\n\n01\n@STAGE\nRESET_MICRO\n@VERSION\n@ZF\nRESET_MICRO\n@VERSION\n@ZF\nRESET_MICRO"), vec![(8, "Duplicate version".to_string())]);
+
+        // 33 stages
+        assert_eq!(titles(&format!("a\n\n01\n{}RESET_MICRO", "@STAGE\n".repeat(32))), vec![(33, "No RESET_MICRO path".to_string()), (34, "Too many stages".to_string())]);
+    }
+}
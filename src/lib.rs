@@ -0,0 +1,13 @@
+// smiscasm as a library: assemble code in-process (see `assembler::assembler::assemble`) or use the instruction set tooling.
+
+pub mod util;
+pub mod instruction;
+pub mod assembler;
+pub mod help;
+pub mod config;
+pub mod library;
+pub mod manifest;
+pub mod output;
+pub mod cli;
+pub mod lsp;
+pub mod formatter;
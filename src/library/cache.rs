@@ -0,0 +1,232 @@
+// Bookkeeping for downloaded libraries.
+// Every cache directory (the local pub-libs/ and the shared per-user one) has an index file
+// that remembers where each library came from, its hash and when it was fetched.
+
+use std::error;
+use std::env;
+use std::fs::*;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use reqwest::get;
+use sha2::{Digest, Sha256};
+use crate::config::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub name: String,       // The file name in the cache directory (e.g. bscmath.s)
+    pub source: String,     // The URL the library was downloaded from, empty if unknown
+    pub hash: String,       // SHA-256 of the contents (hex)
+    pub fetched: u64,       // Seconds since the UNIX epoch
+}
+
+impl CacheEntry {
+    /// Turn a line from an index file into an entry.
+    pub fn from_index_line(line: &str) -> Option<CacheEntry> {
+        let parts = line.split('\t').collect::<Vec<&str>>();
+
+        if parts.len() != 4 { return None; }
+
+        let fetched = parts[3].parse::<u64>().ok()?;
+
+        Some(CacheEntry { name: parts[0].to_string(), source: parts[1].to_string(), hash: parts[2].to_string(), fetched })
+    }
+
+    pub fn to_index_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}", self.name, self.source, self.hash, self.fetched)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LibraryCache {
+    pub directory: PathBuf,
+    pub entries: Vec<CacheEntry>,
+}
+
+impl LibraryCache {
+    /// The pub-libs directory next to where smiscasm was run.
+    pub fn local() -> LibraryCache {
+        LibraryCache::open(PathBuf::from(PUBLIC_LIBS_DIR))
+    }
+
+    /// The cache that is shared between all projects of the current user.
+    pub fn shared() -> Option<LibraryCache> {
+        Some(LibraryCache::open(shared_cache_dir()?))
+    }
+
+    /// Read the index of a cache directory. Files without an index entry are added with an unknown source.
+    pub fn open(directory: PathBuf) -> LibraryCache {
+        let mut entries: Vec<CacheEntry> = vec![];
+
+        if let Ok(index) = read_to_string(directory.join(LIBRARY_INDEX_FILE_NAME)) {
+            entries = index.lines().filter_map(CacheEntry::from_index_line).collect();
+        }
+
+        // Only keep the entries whose files still exist
+        entries.retain(|x| directory.join(&x.name).is_file());
+
+        // Files that were downloaded before there was an index
+        if let Ok(files) = read_dir(&directory) {
+            for file in files.flatten() {
+                let name = file.file_name().to_string_lossy().to_string();
+
+                if name == LIBRARY_INDEX_FILE_NAME || !file.path().is_file() { continue; }
+                if entries.iter().any(|x| x.name == name) { continue; }
+
+                let contents = read(file.path()).unwrap_or_default();
+                let fetched = file.metadata().ok().and_then(|x| x.modified().ok()).map(seconds_since_epoch).unwrap_or(0);
+                let source = KNOWN_PUBLIC_LIBS.iter().find(|x| x.2 == name).map(|x| x.1.to_string()).unwrap_or_default();
+
+                entries.push(CacheEntry { name, source, hash: hash_of(&contents), fetched });
+            }
+        }
+
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        LibraryCache { directory, entries }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CacheEntry> {
+        self.entries.iter().find(|x| x.name == name)
+    }
+
+    pub fn path_of(&self, name: &str) -> PathBuf {
+        self.directory.join(name)
+    }
+
+    /// Store a library in the cache and remember where it came from.
+    pub fn store(&mut self, name: &str, source: &str, contents: &[u8]) -> std::io::Result<()> {
+        create_dir_all(&self.directory)?;
+
+        let mut file = File::create(self.path_of(name))?;
+        file.write_all(contents)?;
+
+        let entry = CacheEntry { name: name.to_string(), source: source.to_string(), hash: hash_of(contents), fetched: seconds_since_epoch(SystemTime::now()) };
+
+        self.entries.retain(|x| x.name != name);
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.write_index()
+    }
+
+    /// Copy an entry (including its metadata) from another cache into this one.
+    pub fn copy_from(&mut self, other: &LibraryCache, name: &str) -> std::io::Result<()> {
+        let entry = other.get(name).cloned().ok_or(std::io::ErrorKind::NotFound)?;
+
+        create_dir_all(&self.directory)?;
+        copy(other.path_of(name), self.path_of(name))?;
+
+        self.entries.retain(|x| x.name != name);
+        self.entries.push(entry);
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        self.write_index()
+    }
+
+    /// Remove the whole cache directory.
+    pub fn clean(&mut self) -> std::io::Result<()> {
+        if self.directory.exists() {
+            remove_dir_all(&self.directory)?;
+        }
+        self.entries.clear();
+
+        Ok(())
+    }
+
+    fn write_index(&self) -> std::io::Result<()> {
+        let index = self.entries.iter().map(|x| x.to_index_line()).collect::<Vec<String>>().join("\n");
+
+        write(self.directory.join(LIBRARY_INDEX_FILE_NAME), index + "\n")
+    }
+}
+
+/// Where the shared library cache lives ($XDG_CACHE_HOME or ~/.cache).
+pub fn shared_cache_dir() -> Option<PathBuf> {
+    if let Ok(cache_home) = env::var("XDG_CACHE_HOME") && !cache_home.is_empty() {
+        return Some(PathBuf::from(cache_home).join(SHARED_LIBS_DIR));
+    }
+
+    let home = env::var("HOME").ok()?;
+
+    Some(PathBuf::from(home).join(".cache").join(SHARED_LIBS_DIR))
+}
+
+pub async fn download(url: String) -> Result<Vec<u8>, Box<dyn error::Error>> {
+    let response = get(url).await?.error_for_status()?;
+    let content = response.bytes().await?;
+
+    Ok(content.to_vec())
+}
+
+/// Remember a freshly downloaded library in the local and the shared cache.
+pub fn record_download(name: &str, source: &str, contents: &[u8]) -> std::io::Result<()> {
+    LibraryCache::local().store(name, source, contents)?;
+
+    // The shared cache is a nice-to-have, don't fail if it can't be written.
+    if let Some(mut shared) = LibraryCache::shared() {
+        let _ = shared.store(name, source, contents);
+    }
+
+    Ok(())
+}
+
+/// Copy a library from the shared cache into pub-libs/ and return its contents if it's there.
+pub fn take_from_shared(name: &str) -> Option<String> {
+    let shared = LibraryCache::shared()?;
+    shared.get(name)?;
+
+    let mut local = LibraryCache::local();
+    local.copy_from(&shared, name).ok()?;
+
+    read_to_string(local.path_of(name)).ok()
+}
+
+pub fn hash_of(contents: &[u8]) -> String {
+    Sha256::digest(contents).iter().map(|x| format!("{:02x}", x)).collect::<Vec<String>>().join("")
+}
+
+fn seconds_since_epoch(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+/// Format seconds since the UNIX epoch like "2025-10-19 13:37 UTC".
+pub fn format_timestamp(seconds: u64) -> String {
+    if seconds == 0 { return "unknown".to_string(); }
+
+    // Civil date from days (see Howard Hinnant's "chrono-Compatible Low-Level Date Algorithms")
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    let seconds_in_day = seconds % 86400;
+
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, seconds_in_day / 3600, (seconds_in_day % 3600) / 60)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::library::cache::{format_timestamp, hash_of, CacheEntry};
+
+    #[test]
+    fn test_index_line() {
+        let entry = CacheEntry { name: "bscmath.s".to_string(), source: "https://example.com/bscmath.s".to_string(), hash: hash_of(b"hlt"), fetched: 1760880000 };
+
+        assert_eq!(CacheEntry::from_index_line(&entry.to_index_line()), Some(entry));
+        assert_eq!(CacheEntry::from_index_line("bscmath.s\tno time"), None);
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "unknown");
+        assert_eq!(format_timestamp(951782400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1760880000 + 3600 + 120), "2025-10-19 14:22 UTC");
+    }
+}
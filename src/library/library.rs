@@ -0,0 +1,169 @@
+use colorize::AnsiColor;
+use clap::Subcommand;
+use crate::config::*;
+use crate::library::cache::*;
+use crate::util::exit::{exit, ExitCode};
+
+#[derive(Debug, PartialEq, Subcommand)]
+pub enum LibCommand {
+    /// Show all cached libraries with their source, hash and when they were fetched
+    List,
+
+    /// Download cached libraries again (all of them if no name is given)
+    Update { name: Option<String> },
+
+    /// Remove pub-libs/ (and the shared cache with --shared)
+    Clean {
+        #[clap(long)]
+        shared: bool,
+    },
+
+    /// Copy libraries from the shared cache into pub-libs/ (all of them if no name is given)
+    Vendor { names: Vec<String> },
+}
+
+pub async fn run_lib_command(command: LibCommand) {
+    match command {
+        LibCommand::List => list(),
+        LibCommand::Update { name } => update(name).await,
+        LibCommand::Clean { shared } => clean(shared),
+        LibCommand::Vendor { names } => vendor(names).await,
+    }
+}
+
+fn list() {
+    print_cache("Local", &LibraryCache::local());
+
+    if let Some(shared) = LibraryCache::shared() {
+        print_cache("Shared", &shared);
+    }
+}
+
+fn print_cache(title: &str, cache: &LibraryCache) {
+    println!("{}", format!("{} ({}):", title, cache.directory.display()).bold());
+
+    if cache.entries.is_empty() {
+        println!("    {}\n", "No libraries".yellow());
+        return;
+    }
+
+    for entry in cache.entries.iter() {
+        let source = if entry.source.is_empty() { "unknown source".to_string() } else { entry.source.clone() };
+
+        println!("    {:<16} {}  {}  {}", entry.name, entry.hash.get(..12).unwrap_or(&entry.hash).to_string().blue(), format_timestamp(entry.fetched), source);
+    }
+
+    println!();
+}
+
+async fn update(name: Option<String>) {
+    let local = LibraryCache::local();
+
+    let mut names = local.entries.iter().map(|x| x.name.clone()).collect::<Vec<String>>();
+
+    if let Some(name) = name {
+        let name = file_name_for(&name);
+
+        if local.get(&name).is_none() && known_source_for(&name).is_none() {
+            exit(format!("There is no library named {} in {} and it isn't a well-known public library.", name, PUBLIC_LIBS_DIR), ExitCode::BadArgument);
+        }
+
+        names = vec![name];
+    }
+
+    if names.is_empty() {
+        println!("{}", "Nothing to update".yellow());
+        return;
+    }
+
+    for name in names {
+        let old_entry = local.get(&name).cloned();
+        let source = old_entry.as_ref().map(|x| x.source.clone()).filter(|x| !x.is_empty()).or(known_source_for(&name));
+
+        let Some(source) = source else {
+            println!("{}: {}", name, "skipped, source unknown".yellow());
+            continue;
+        };
+
+        print!("Updating {} from {} ... ", name, source);
+
+        let Ok(contents) = download(source.clone()).await else {
+            println!("{}", "failed".red());
+            continue;
+        };
+
+        if record_download(&name, &source, &contents).is_err() {
+            exit(format!("Couldn't store library {} in {}.", name, PUBLIC_LIBS_DIR), ExitCode::ReadWriteError);
+        }
+
+        if old_entry.map(|x| x.hash) == Some(hash_of(&contents)) {
+            println!("{}", "unchanged".green());
+        } else {
+            println!("{}", "updated".green());
+        }
+    }
+}
+
+fn clean(shared: bool) {
+    if LibraryCache::local().clean().is_err() {
+        exit(format!("Couldn't remove {}.", PUBLIC_LIBS_DIR), ExitCode::ReadWriteError);
+    }
+
+    if shared && let Some(mut shared_cache) = LibraryCache::shared() && shared_cache.clean().is_err() {
+        exit(format!("Couldn't remove {}.", shared_cache.directory.display()), ExitCode::ReadWriteError);
+    }
+
+    println!("{}", "Library cache removed".green());
+}
+
+async fn vendor(names: Vec<String>) {
+    let Some(shared) = LibraryCache::shared() else {
+        exit("Couldn't find the shared library cache. Is $HOME set?".to_string(), ExitCode::Other);
+        return;
+    };
+
+    let mut local = LibraryCache::local();
+
+    let names = if names.is_empty() {
+        shared.entries.iter().map(|x| x.name.clone()).collect::<Vec<String>>()
+    } else {
+        names.iter().map(|x| file_name_for(x)).collect()
+    };
+
+    for name in names {
+        if shared.get(&name).is_some() {
+            if local.copy_from(&shared, &name).is_err() {
+                exit(format!("Couldn't copy {} into {}.", name, PUBLIC_LIBS_DIR), ExitCode::ReadWriteError);
+            }
+            println!("{}: {}", name, "vendored".green());
+            continue;
+        }
+
+        // Not in the shared cache, but maybe it can be downloaded.
+        let Some(source) = known_source_for(&name) else {
+            exit(format!("There is no library named {} in the shared cache and it isn't a well-known public library.", name), ExitCode::BadArgument);
+            return;
+        };
+
+        let Ok(contents) = download(source.clone()).await else {
+            exit(format!("Download of library {} from {} failed.", name, source), ExitCode::Other);
+            return;
+        };
+
+        if record_download(&name, &source, &contents).is_err() {
+            exit(format!("Couldn't store library {} in {}.", name, PUBLIC_LIBS_DIR), ExitCode::ReadWriteError);
+        }
+
+        local = LibraryCache::local();
+        println!("{}: {}", name, "downloaded".green());
+    }
+}
+
+/// Well-known libraries can be named without their suffix (bscmath instead of bscmath.s).
+fn file_name_for(name: &str) -> String {
+    KNOWN_PUBLIC_LIBS.iter().find(|x| x.0 == name).map(|x| x.2.to_string()).unwrap_or(name.to_string())
+}
+
+fn known_source_for(file_name: &str) -> Option<String> {
+    KNOWN_PUBLIC_LIBS.iter().find(|x| x.2 == file_name).map(|x| x.1.to_string())
+}
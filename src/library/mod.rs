@@ -0,0 +1,2 @@
+pub mod library;
+pub mod cache;
\ No newline at end of file
@@ -0,0 +1,285 @@
+// What the language server knows about SMISC sources besides diagnostics: definitions, names under the cursor, hovers & completions.
+// This only looks at the text (it doesn't assemble), so it also works while the code is broken.
+// Lines & columns are 0-based like in LSP, columns count characters.
+
+use crate::assembler::include::local_include_path;
+use crate::help::reference::describe_instruction;
+use crate::instruction::instruction::Instruction;
+use crate::util::file_system::FileSystem;
+use crate::util::remove_comments::remove_comments_in_line;
+
+/// The commands after a "." that aren't constants.
+pub const ASSEMBLER_COMMANDS: [&str; 6] = ["section", "mode", "global", "ascii", "stc", "incbin"];
+
+/// The modifiers that can follow a label (after an @) and what they mean.
+pub const MODIFIERS: [(&str, &str); 3] = [
+    ("PAGE", "The memory page the label is in"),
+    ("PAGEOFF", "The label's offset within its memory page"),
+    ("RELATIVE", "The label's offset to the next instruction"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefinitionKind {
+    Label,
+    Constant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: String,
+    pub kind: DefinitionKind,
+    pub file_name: String,          // The file it's defined in (as it's included or opened)
+    pub line: u32,
+    pub column: u32,
+    pub value: Option<String>,      // The value as written (constants only)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind {
+    Instruction,
+    Register,
+    Modifier,
+    AssemblerCommand,
+    Label,
+    Constant,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|x| x.is_alphanumeric() || x == '_')
+}
+
+/// The column (in characters) of the byte index in the line.
+fn column_of(line: &str, byte_index: usize) -> u32 {
+    line[..byte_index].chars().count() as u32
+}
+
+/// The labels & constants defined in a file (not in the files it includes).
+pub fn file_definitions(code: &str, file_name: &str) -> Vec<Definition> {
+    let mut result = Vec::new();
+
+    for (line_number, line) in code.lines().enumerate() {
+        let contents = remove_comments_in_line(line.to_string());
+        let trimmed = contents.trim();
+        let start = contents.len() - contents.trim_start().len();
+
+        // main:
+        if let Some(name) = trimmed.strip_suffix(':') && is_name(name) {
+            result.push(Definition { name: name.to_string(), kind: DefinitionKind::Label, file_name: file_name.to_string(), line: line_number as u32, column: column_of(&contents, start), value: None });
+            continue;
+        }
+
+        // .name value
+        if let Some(rest) = trimmed.strip_prefix('.') {
+            let mut parts = rest.trim_start().splitn(2, char::is_whitespace);
+            let name = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("").trim();
+
+            if !is_name(name) || ASSEMBLER_COMMANDS.contains(&name) { continue; }
+
+            let name_start = start + 1 + rest.find(name).unwrap();
+            result.push(Definition { name: name.to_string(), kind: DefinitionKind::Constant, file_name: file_name.to_string(), line: line_number as u32, column: column_of(&contents, name_start), value: Some(value.to_string()) });
+        }
+    }
+
+    result
+}
+
+/// The labels & constants in a file and in everything it includes (as far as it's on disk already).
+pub fn definitions(code: &str, file_name: &str, files: &dyn FileSystem) -> Vec<Definition> {
+    let mut visited = vec![file_name.to_string()];
+
+    collect_definitions(code, file_name, files, &mut visited)
+}
+
+fn collect_definitions(code: &str, file_name: &str, files: &dyn FileSystem, visited: &mut Vec<String>) -> Vec<Definition> {
+    let mut result = file_definitions(code, file_name);
+
+    for line in code.lines() {
+        let Some(inclusion_argument) = line.strip_prefix("!include") else { continue; };
+        let Some(path) = local_include_path(inclusion_argument.trim(), files) else { continue; };
+
+        // Files including each other would never end
+        if visited.contains(&path) { continue; }
+        visited.push(path.clone());
+
+        if let Some(contents) = files.read_to_string(path.as_str()) {
+            result.extend(collect_definitions(&contents, &path, files, visited));
+        }
+    }
+
+    result
+}
+
+/// The name the character is in (or right after) and where it starts & ends.
+/// Modifiers belong to the label before them (the name under "msg@PAGE" is always "msg").
+pub fn name_at(line: &str, character: u32) -> Option<(String, u32, u32)> {
+    let chars = line.chars().collect::<Vec<char>>();
+    let is_name_char = |x: char| x.is_alphanumeric() || x == '_';
+
+    let position = (character as usize).min(chars.len());
+    let mut start = position;
+    let mut end = position;
+
+    while start > 0 && is_name_char(chars[start - 1]) { start -= 1; }
+    while end < chars.len() && is_name_char(chars[end]) { end += 1; }
+
+    if start > 0 && chars[start - 1] == '@' {
+        return name_at(line, start as u32 - 1);
+    }
+
+    if start == end { return None; }
+
+    Some((chars[start..end].iter().collect(), start as u32, end as u32))
+}
+
+/// Where the name under the cursor is defined.
+pub fn definition_at<'a>(line: &str, character: u32, definitions: &'a [Definition]) -> Option<&'a Definition> {
+    let (name, _, _) = name_at(line, character)?;
+
+    definitions.iter().find(|x| x.name == name)
+}
+
+/// Markdown describing the name under the cursor: a constant's value, where a label is or an instruction's formats.
+pub fn hover(line: &str, character: u32, definitions: &[Definition], instructions: &[Instruction]) -> Option<String> {
+    let (name, _, _) = name_at(line, character)?;
+
+    if let Some(definition) = definitions.iter().find(|x| x.name == name) {
+        let place = format!("{}, line {}", definition.file_name, definition.line + 1);
+
+        return Some(match definition.kind {
+            DefinitionKind::Constant => format!("```\n.{} {}\n```\nConstant defined in {}", definition.name, definition.value.clone().unwrap_or_default(), place),
+            DefinitionKind::Label => format!("```\n{}:\n```\nLabel defined in {}", definition.name, place),
+        });
+    }
+
+    if instructions.iter().any(|x| x.name == name) {
+        return describe_instruction(&name, instructions);
+    }
+
+    None
+}
+
+/// What could be written at the cursor: modifiers after an @, assembler commands after a ".", instructions at the start of a line
+/// and registers, labels & constants in arguments.
+pub fn completions(line: &str, character: u32, definitions: &[Definition], instructions: &[Instruction]) -> Vec<Completion> {
+    let before = line.chars().take(character as usize).collect::<String>();
+    let before_name = before.trim_end_matches(|x: char| x.is_alphanumeric() || x == '_');
+
+    if before_name.ends_with('@') {
+        return MODIFIERS.iter().map(|x| Completion { label: x.0.to_string(), kind: CompletionKind::Modifier, detail: x.1.to_string() }).collect();
+    }
+
+    if before_name.trim() == "." {
+        return ASSEMBLER_COMMANDS.iter().map(|x| Completion { label: x.to_string(), kind: CompletionKind::AssemblerCommand, detail: format!(".{}", x) }).collect();
+    }
+
+    // Nothing to complete in comments, includes & other assembler commands
+    if before_name.contains('#') || before_name.trim_start().starts_with(['.', '!']) { return Vec::new(); }
+
+    if before_name.trim().is_empty() {
+        let mut names = instructions.iter().map(|x| x.name.clone()).collect::<Vec<String>>();
+        names.sort();
+        names.dedup();
+
+        return names.into_iter().map(|name| {
+            let formats = instructions.iter().filter(|x| x.name == name).map(|x| format!("{} {}", name, x.format_notation()).trim_end().to_string()).collect::<Vec<String>>();
+            Completion { label: name, kind: CompletionKind::Instruction, detail: formats.join(" | ") }
+        }).collect();
+    }
+
+    let mut result = (0..32).map(|x| Completion { label: format!("x{}", x), kind: CompletionKind::Register, detail: "Register".to_string() }).collect::<Vec<Completion>>();
+    result.push(Completion { label: "sp".to_string(), kind: CompletionKind::Register, detail: "Stack pointer (x31)".to_string() });
+
+    for definition in definitions.iter() {
+        if result.iter().any(|x| x.label == definition.name) { continue; }
+
+        let completion = match definition.kind {
+            DefinitionKind::Label => Completion { label: definition.name.clone(), kind: CompletionKind::Label, detail: format!("Label in {}", definition.file_name) },
+            DefinitionKind::Constant => Completion { label: definition.name.clone(), kind: CompletionKind::Constant, detail: format!(".{} {}", definition.name, definition.value.clone().unwrap_or_default()) },
+        };
+
+        result.push(completion);
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::lsp::analysis::{completions, definition_at, definitions, file_definitions, hover, name_at, CompletionKind, DefinitionKind};
+    use crate::util::file_system::MemoryFileSystem;
+
+    const CODE: &str = ".section \"CODE\"\n.mode text\n.msg_len 13 # The length\n\n_main:\n    adrp x0, msg@PAGE\n    add x1, msg_len\n!include lib.s\n";
+
+    #[test]
+    fn test_file_definitions() {
+        let definitions = file_definitions(CODE, "main.s");
+
+        assert_eq!(definitions.len(), 2);
+        assert_eq!((definitions[0].name.as_str(), definitions[0].kind, definitions[0].line, definitions[0].column), ("msg_len", DefinitionKind::Constant, 2, 1));
+        assert_eq!(definitions[0].value, Some("13".to_string()));
+        assert_eq!((definitions[1].name.as_str(), definitions[1].kind, definitions[1].line, definitions[1].column), ("_main", DefinitionKind::Label, 4, 0));
+    }
+
+    #[test]
+    fn test_definitions_in_includes() {
+        let mut files = MemoryFileSystem::new();
+        files.add("pub-libs/lib.s", ".mode data\n  msg:\n    .ascii \"Hello, World!\"\n!include main.s\n");
+        files.add("main.s", CODE);
+
+        let definitions = definitions(CODE, "main.s", &files);
+        let message = definitions.iter().find(|x| x.name == "msg").unwrap();
+
+        assert_eq!(definitions.len(), 3);
+        assert_eq!((message.file_name.as_str(), message.line, message.column), ("pub-libs/lib.s", 1, 2));
+    }
+
+    #[test]
+    fn test_name_at() {
+        assert_eq!(name_at("    adrp x0, msg@PAGE", 14), Some(("msg".to_string(), 13, 16)));
+        assert_eq!(name_at("    adrp x0, msg@PAGE", 18), Some(("msg".to_string(), 13, 16)));
+        assert_eq!(name_at("    adrp x0, msg@PAGE", 6), Some(("adrp".to_string(), 4, 8)));
+        assert_eq!(name_at("    adrp x0, msg@PAGE", 2), None);
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let definitions = file_definitions(CODE, "main.s");
+        let instructions = get_all_instructions();
+
+        assert_eq!(definition_at("    add x1, msg_len", 15, &definitions).unwrap().line, 2);
+        assert!(hover("    add x1, msg_len", 15, &definitions, &instructions).unwrap().contains(".msg_len 13"));
+        assert!(hover("    add x1, msg_len", 5, &definitions, &instructions).unwrap().contains("add"));
+        assert_eq!(hover("    add x1, nothing", 15, &definitions, &instructions), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let definitions = file_definitions(CODE, "main.s");
+        let instructions = get_all_instructions();
+
+        let at_start = completions("    ad", 6, &definitions, &instructions);
+        assert!(at_start.iter().all(|x| x.kind == CompletionKind::Instruction));
+        assert!(at_start.iter().any(|x| x.label == "add" && x.detail.contains('|')));
+
+        let arguments = completions("    add x1, ", 12, &definitions, &instructions);
+        assert!(arguments.iter().any(|x| x.label == "x31" && x.kind == CompletionKind::Register));
+        assert!(arguments.iter().any(|x| x.label == "msg_len" && x.kind == CompletionKind::Constant));
+        assert!(arguments.iter().any(|x| x.label == "_main" && x.kind == CompletionKind::Label));
+
+        let modifiers = completions("    adrp x0, msg@PA", 19, &definitions, &instructions);
+        assert_eq!(modifiers.iter().map(|x| x.label.as_str()).collect::<Vec<&str>>(), vec!["PAGE", "PAGEOFF", "RELATIVE"]);
+
+        assert!(completions(".", 1, &definitions, &instructions).iter().any(|x| x.label == "section"));
+        assert!(completions("    add x1, 5 # x", 17, &definitions, &instructions).is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+// `smiscasm lsp`: a language server for SMISC assembly, speaking LSP over stdio.
+// Diagnostics come from assembling the file (when it's opened & saved), everything else from `lsp::analysis`.
+// Paths inside the working directory are handled relative to it, just like the CLI & !include do.
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::str::FromStr;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::*;
+use serde_json::Value;
+use url::Url;
+use crate::assembler::assembler::{assemble, AssembleOptions};
+use crate::assembler::include::local_include_path;
+use crate::instruction::instruction::{get_all_instructions, Instruction};
+use crate::lsp::analysis::{completions, definition_at, definitions, file_definitions, hover, CompletionKind, Definition, DefinitionKind};
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::Span;
+use crate::util::exit::{exit, ExitCode};
+use crate::util::file_system::{DiskFileSystem, FileSystem};
+
+/// The open documents (by path) on top of the disk, so unsaved changes are seen everywhere.
+struct Documents {
+    open: HashMap<String, String>,
+}
+
+impl FileSystem for Documents {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        match self.open.get(path) {
+            Some(contents) => Some(contents.clone().into_bytes()),
+            None => DiskFileSystem.read(path),
+        }
+    }
+}
+
+pub struct Server {
+    documents: Documents,
+    instructions: Vec<Instruction>,
+    published: HashMap<String, Vec<Uri>>,   // The files each open document's diagnostics were published for (to clear them later)
+}
+
+/// Run the language server on stdin & stdout until the client shuts it down.
+pub fn run_language_server() {
+    let (connection, io_threads) = Connection::stdio();
+
+    if let Err(error) = serve(&connection, get_all_instructions()) {
+        exit(format!("The language server failed: {}", error), ExitCode::Internal);
+    }
+
+    drop(connection);
+    io_threads.join().ok();
+}
+
+/// Initialize and answer messages until the client shuts the server down.
+pub fn serve(connection: &Connection, instructions: Vec<Instruction>) -> Result<(), Box<dyn Error + Sync + Send>> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+
+    let mut server = Server { documents: Documents { open: HashMap::new() }, instructions, published: HashMap::new() };
+
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? { return Ok(()); }
+
+                connection.sender.send(Message::Response(server.handle_request(request)))?;
+            }
+
+            Message::Notification(notification) => {
+                for notification in server.handle_notification(notification) {
+                    connection.sender.send(Message::Notification(notification))?;
+                }
+            }
+
+            Message::Response(_) => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+            open_close: Some(true),
+            change: Some(TextDocumentSyncKind::FULL),
+            save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+            ..Default::default()
+        })),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions { trigger_characters: Some(vec!["@".to_string(), ".".to_string()]), ..Default::default() }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+/// The path of a file URI (relative to the working directory if it's in there).
+fn uri_to_path(uri: &Uri) -> Option<String> {
+    let path = Url::parse(uri.as_str()).ok()?.to_file_path().ok()?;
+    let working_directory = env::current_dir().ok()?;
+    let path = path.strip_prefix(&working_directory).map(|x| x.to_path_buf()).unwrap_or(path);
+
+    Some(path.to_str()?.to_string())
+}
+
+fn path_to_uri(path: &str) -> Option<Uri> {
+    let path = env::current_dir().ok()?.join(path);
+
+    Uri::from_str(Url::from_file_path(path).ok()?.as_str()).ok()
+}
+
+fn range(line: u32, start: u32, end: u32) -> Range {
+    Range { start: Position { line, character: start }, end: Position { line, character: end } }
+}
+
+/// Where a span is in its file (the whole line if there's no column).
+fn span_range(span: &Span) -> Range {
+    let line = span.line.saturating_sub(1);
+
+    match span.column {
+        Some(column) => range(line, span.indent + column, span.indent + column + span.length.unwrap_or(1)),
+        None => range(line, span.indent, span.indent + span.code.chars().count() as u32),
+    }
+}
+
+fn params<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, String> {
+    serde_json::from_value(value).map_err(|x| x.to_string())
+}
+
+impl Server {
+    /// The contents of a document (open or on disk) and its path.
+    fn document(&self, uri: &Uri) -> Option<(String, String)> {
+        let path = uri_to_path(uri)?;
+        let contents = self.documents.read_to_string(path.as_str())?;
+
+        Some((path, contents))
+    }
+
+    /// The line the cursor is in, the definitions it can see and the position.
+    fn context(&self, position: &TextDocumentPositionParams) -> Option<(String, Vec<Definition>, u32)> {
+        let (path, contents) = self.document(&position.text_document.uri)?;
+        let line = contents.lines().nth(position.position.line as usize).unwrap_or("").to_string();
+
+        Some((line, definitions(&contents, &path, &self.documents), position.position.character))
+    }
+
+    pub fn handle_request(&mut self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            "textDocument/definition" => params::<GotoDefinitionParams>(request.params).map(|x| self.definition(x)),
+            "textDocument/hover" => params::<HoverParams>(request.params).map(|x| self.hover(x)),
+            "textDocument/completion" => params::<CompletionParams>(request.params).map(|x| self.completion(x)),
+            "textDocument/documentSymbol" => params::<DocumentSymbolParams>(request.params).map(|x| self.document_symbols(x)),
+            _ => return Response::new_err(request.id, ErrorCode::MethodNotFound as i32, format!("Unknown method: {}", request.method)),
+        };
+
+        match result {
+            Ok(result) => Response::new_ok(request.id, result),
+            Err(message) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, message),
+        }
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Value {
+        let Some((line, definitions, character)) = self.context(&params.text_document_position_params) else { return Value::Null; };
+        let Some(definition) = definition_at(&line, character, &definitions) else { return Value::Null; };
+        let Some(uri) = path_to_uri(&definition.file_name) else { return Value::Null; };
+
+        let end = definition.column + definition.name.chars().count() as u32;
+        serde_json::to_value(Location::new(uri, range(definition.line, definition.column, end))).unwrap()
+    }
+
+    fn hover(&self, params: HoverParams) -> Value {
+        let Some((line, definitions, character)) = self.context(&params.text_document_position_params) else { return Value::Null; };
+        let Some(text) = hover(&line, character, &definitions, &self.instructions) else { return Value::Null; };
+
+        serde_json::to_value(Hover { contents: HoverContents::Markup(MarkupContent { kind: MarkupKind::Markdown, value: text }), range: None }).unwrap()
+    }
+
+    fn completion(&self, params: CompletionParams) -> Value {
+        let Some((line, definitions, character)) = self.context(&params.text_document_position) else { return Value::Null; };
+
+        let items = completions(&line, character, &definitions, &self.instructions).into_iter().map(|x| {
+            let kind = match x.kind {
+                CompletionKind::Instruction | CompletionKind::AssemblerCommand => CompletionItemKind::KEYWORD,
+                CompletionKind::Register => CompletionItemKind::VARIABLE,
+                CompletionKind::Modifier => CompletionItemKind::PROPERTY,
+                CompletionKind::Label => CompletionItemKind::FUNCTION,
+                CompletionKind::Constant => CompletionItemKind::CONSTANT,
+            };
+
+            CompletionItem { label: x.label, kind: Some(kind), detail: Some(x.detail), ..Default::default() }
+        }).collect::<Vec<CompletionItem>>();
+
+        serde_json::to_value(CompletionResponse::Array(items)).unwrap()
+    }
+
+    /// The labels in the document.
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Value {
+        let Some((path, contents)) = self.document(&params.text_document.uri) else { return Value::Null; };
+
+        #[allow(deprecated)]
+        let symbols = file_definitions(&contents, &path).into_iter().filter(|x| x.kind == DefinitionKind::Label).map(|x| {
+            let end = x.column + x.name.chars().count() as u32;
+            DocumentSymbol { name: x.name, detail: None, kind: SymbolKind::FUNCTION, tags: None, deprecated: None, range: range(x.line, x.column, end + 1), selection_range: range(x.line, x.column, end), children: None }
+        }).collect::<Vec<DocumentSymbol>>();
+
+        serde_json::to_value(DocumentSymbolResponse::Nested(symbols)).unwrap()
+    }
+
+    /// Keep track of the documents. Returns the notifications to send (diagnostics).
+    pub fn handle_notification(&mut self, notification: Notification) -> Vec<Notification> {
+        match notification.method.as_str() {
+            "textDocument/didOpen" => {
+                let Ok(params) = params::<DidOpenTextDocumentParams>(notification.params) else { return Vec::new(); };
+                let Some(path) = uri_to_path(&params.text_document.uri) else { return Vec::new(); };
+
+                self.documents.open.insert(path.clone(), params.text_document.text);
+                self.diagnose(path)
+            }
+
+            "textDocument/didChange" => {
+                let Ok(params) = params::<DidChangeTextDocumentParams>(notification.params) else { return Vec::new(); };
+                let Some(path) = uri_to_path(&params.text_document.uri) else { return Vec::new(); };
+
+                // Only full syncs are supported, so the last change is the whole document.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.open.insert(path, change.text);
+                }
+
+                Vec::new()
+            }
+
+            "textDocument/didSave" => {
+                let Ok(params) = params::<DidSaveTextDocumentParams>(notification.params) else { return Vec::new(); };
+                let Some(path) = uri_to_path(&params.text_document.uri) else { return Vec::new(); };
+
+                if let Some(text) = params.text { self.documents.open.insert(path.clone(), text); }
+                self.diagnose(path)
+            }
+
+            "textDocument/didClose" => {
+                let Ok(params) = params::<DidCloseTextDocumentParams>(notification.params) else { return Vec::new(); };
+                let Some(path) = uri_to_path(&params.text_document.uri) else { return Vec::new(); };
+
+                self.documents.open.remove(&path);
+                let published = self.published.remove(&path).unwrap_or_default();
+
+                published.into_iter().map(|uri| publish(uri, Vec::new())).collect()
+            }
+
+            _ => Vec::new(),
+        }
+    }
+
+    /// Assemble a document and publish its errors & warnings (for it and the files it includes).
+    fn diagnose(&mut self, path: String) -> Vec<Notification> {
+        let Some(code) = self.documents.open.get(&path).cloned() else { return Vec::new(); };
+
+        // Nothing is downloaded while typing, only libraries that are already there are used.
+        let options = AssembleOptions::new(path.clone());
+        let result = futures::executor::block_on(assemble(code, self.instructions.clone(), &self.documents, options));
+        let diagnostics = match result { Ok(assembly) => assembly.diagnostics, Err(error) => error.diagnostics };
+
+        let mut by_file: Vec<(Uri, Vec<lsp_types::Diagnostic>)> = Vec::new();
+
+        // Start with the document itself, so it's cleared if there's nothing wrong.
+        if let Some(uri) = path_to_uri(&path) { by_file.push((uri, Vec::new())); }
+
+        for diagnostic in diagnostics.iter() {
+            // Mistakes that aren't in a specific line are shown at the start of the document.
+            let (file_name, range) = match &diagnostic.primary {
+                Some(span) => (self.file_of(span), span_range(span)),
+                None => (path.clone(), range(0, 0, 0)),
+            };
+
+            let Some(uri) = path_to_uri(&file_name) else { continue; };
+
+            let related = diagnostic.secondary.iter().filter_map(|(span, label)| {
+                Some(DiagnosticRelatedInformation { location: Location::new(path_to_uri(&self.file_of(span))?, span_range(span)), message: label.clone() })
+            }).collect::<Vec<DiagnosticRelatedInformation>>();
+
+            let mut message = diagnostic.message.clone();
+            diagnostic.notes.iter().for_each(|x| message += &format!("\nNote: {}", x));
+
+            let converted = lsp_types::Diagnostic {
+                range,
+                severity: Some(match diagnostic.severity { ErrorNotificationKind::Error => DiagnosticSeverity::ERROR, ErrorNotificationKind::Warning => DiagnosticSeverity::WARNING }),
+                code: diagnostic.code.clone().map(NumberOrString::String),
+                source: Some("smiscasm".to_string()),
+                message: format!("{}: {}", diagnostic.title, message),
+                related_information: if related.is_empty() { None } else { Some(related) },
+                ..Default::default()
+            };
+
+            match by_file.iter_mut().find(|x| x.0 == uri) {
+                Some(file) => file.1.push(converted),
+                None => by_file.push((uri, vec![converted])),
+            }
+        }
+
+        // Clear files that had diagnostics last time, but don't anymore
+        let previous = self.published.insert(path, by_file.iter().map(|x| x.0.clone()).collect()).unwrap_or_default();
+        let cleared = previous.into_iter().filter(|x| !by_file.iter().any(|y| y.0 == *x)).map(|x| (x, Vec::new())).collect::<Vec<(Uri, Vec<lsp_types::Diagnostic>)>>();
+
+        by_file.into_iter().chain(cleared).map(|(uri, diagnostics)| publish(uri, diagnostics)).collect()
+    }
+
+    /// The file a span is in (included files are named by their !include argument).
+    fn file_of(&self, span: &Span) -> String {
+        local_include_path(&span.file_name, &self.documents).unwrap_or(span.file_name.clone())
+    }
+}
+
+fn publish(uri: Uri, diagnostics: Vec<lsp_types::Diagnostic>) -> Notification {
+    Notification::new("textDocument/publishDiagnostics".to_string(), PublishDiagnosticsParams { uri, diagnostics, version: None })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use lsp_server::{Connection, Message, Notification, Request, RequestId};
+    use serde_json::{json, Value};
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::lsp::server::{path_to_uri, serve};
+
+    /// Send a request like a client would and wait for its response (skipping notifications).
+    fn request(client: &Connection, id: i32, method: &str, params: Value) -> Value {
+        client.sender.send(Message::Request(Request::new(RequestId::from(id), method.to_string(), params))).unwrap();
+
+        loop {
+            if let Message::Response(response) = client.receiver.recv().unwrap() && response.id == RequestId::from(id) {
+                return response.result.unwrap_or(Value::Null);
+            }
+        }
+    }
+
+    fn notify(client: &Connection, method: &str, params: Value) {
+        client.sender.send(Message::Notification(Notification::new(method.to_string(), params))).unwrap();
+    }
+
+    /// The next published diagnostics (for any file).
+    fn published(client: &Connection) -> Value {
+        loop {
+            if let Message::Notification(notification) = client.receiver.recv().unwrap() && notification.method == "textDocument/publishDiagnostics" {
+                return notification.params;
+            }
+        }
+    }
+
+    fn position(uri: &str, line: u32, character: u32) -> Value {
+        json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+    }
+
+    #[test]
+    fn test_language_server() {
+        let (server, client) = Connection::memory();
+        let server = thread::spawn(move || serve(&server, get_all_instructions()).unwrap());
+
+        let library = path_to_uri("lsp_test_lib.s").unwrap().as_str().to_string();
+        let main = path_to_uri("lsp_test_main.s").unwrap().as_str().to_string();
+
+        let initialized = request(&client, 1, "initialize", json!({ "capabilities": {} }));
+        assert_eq!(initialized["capabilities"]["hoverProvider"], json!(true));
+        notify(&client, "initialized", json!({}));
+
+        notify(&client, "textDocument/didOpen", json!({ "textDocument": { "uri": library, "languageId": "smisc", "version": 1, "text": ".msg_len 13\n.global msg_len\n" } }));
+        assert_eq!(published(&client)["diagnostics"], json!([]));
+
+        let code = "!include lsp_test_lib.s\n.section \"CODE\"\n.mode text\nMain:\n    add x0, msg_len\n    hlt\n";
+        notify(&client, "textDocument/didOpen", json!({ "textDocument": { "uri": main, "languageId": "smisc", "version": 1, "text": code } }));
+
+        let diagnostics = published(&client);
+        assert_eq!(diagnostics["uri"], json!(main));
+        let naming = diagnostics["diagnostics"].as_array().unwrap().iter().find(|x| x["code"] == json!("W0002")).unwrap();
+        assert_eq!(naming["range"]["start"], json!({ "line": 3, "character": 0 }));
+
+        // Go to the constant in the included file
+        let definition = request(&client, 2, "textDocument/definition", position(&main, 4, 14));
+        assert_eq!(definition["uri"], json!(library));
+        assert_eq!(definition["range"]["start"], json!({ "line": 0, "character": 1 }));
+
+        let hover = request(&client, 3, "textDocument/hover", position(&main, 4, 14));
+        assert!(hover["contents"]["value"].as_str().unwrap().contains(".msg_len 13"));
+
+        let hover = request(&client, 4, "textDocument/hover", position(&main, 4, 5));
+        assert!(hover["contents"]["value"].as_str().unwrap().contains("add"));
+
+        let completions = request(&client, 5, "textDocument/completion", position(&main, 4, 12));
+        assert!(completions.as_array().unwrap().iter().any(|x| x["label"] == json!("msg_len")));
+
+        let symbols = request(&client, 6, "textDocument/documentSymbol", json!({ "textDocument": { "uri": main } }));
+        assert_eq!(symbols.as_array().unwrap().len(), 1);
+        assert_eq!(symbols[0]["name"], json!("Main"));
+
+        // Fixing the mistake clears it on save
+        notify(&client, "textDocument/didChange", json!({ "textDocument": { "uri": main, "version": 2 }, "contentChanges": [{ "text": code.replace("Main:", "_main:") }] }));
+        notify(&client, "textDocument/didSave", json!({ "textDocument": { "uri": main } }));
+        assert_eq!(published(&client)["diagnostics"], json!([]));
+
+        request(&client, 7, "shutdown", Value::Null);
+        notify(&client, "exit", Value::Null);
+        server.join().unwrap();
+    }
+}
@@ -1,71 +1,71 @@
 use colorize::AnsiColor;
 use clap::Parser;
-use std::env;
-use std::fmt::Debug;
 use std::fs;
-use std::path::PathBuf;
-use crate::instruction::instruction::{Instruction, micro_operation_at};
-use crate::assembler::assembler::assemble;
-use crate::help::help::{print_help, print_instruction_help};
+use std::process;
+use smiscasm::instruction::instruction::{get_instruction_definitions, max_op_code, micro_operation_at, use_instruction_set_dir};
+use smiscasm::instruction::allocation::op_code_map;
+use smiscasm::instruction::micro_operation::generate_empty_control_word;
+use smiscasm::instruction::table::{diff_report, generate_control_words, read_control_words, split_control_words, table_file_name};
+use smiscasm::assembler::assembler::{assemble, AssembleOptions};
+use smiscasm::help::help::{print_explanation, print_help, print_instruction_help, print_instruction_list};
 use std::fs::File;
 use std::io::prelude::*;
-use crate::util::exit::{exit, ExitCode};
+use std::path::Path;
+use smiscasm::util::exit::{exit, ExitCode};
+use smiscasm::library::library::run_lib_command;
+use smiscasm::lsp::server::run_language_server;
+use smiscasm::formatter::run_fmt;
+use smiscasm::manifest::read_manifest;
+use smiscasm::output::output::{encode, parse_address, segments_from_sections, OutputFormat};
+use smiscasm::output::table::{encode_table, TableFormat};
+use smiscasm::output::isa_docs::{isa_document, to_html, to_markdown};
+use smiscasm::output::diagnostics::{to_json_lines, to_sarif, MessageFormat};
+use smiscasm::output::dot::{to_dot, CfgFormat};
+use smiscasm::output::timing::{ascii_chart, find_instructions, svg_chart};
+use smiscasm::instruction::encoding::ENCODING;
+use smiscasm::cli::{ArgumentList, Command};
+use smiscasm::util::diagnostic::render_diagnostics;
+use smiscasm::util::file_system::DiskFileSystem;
+use smiscasm::util::path::expand_path;
+use smiscasm::util::warnings::WarningOptions;
 
-mod util;
-mod instruction;
-mod assembler;
-mod help;
-mod config;
+#[tokio::main]
+async fn main() {
+    // Generate a reasonable argument list
+    let mut args = ArgumentList::parse();
 
-#[derive(Debug, PartialEq, Parser)]
-pub struct ArgumentList{
-    pub file: Option<String>,
+    if args.help { print_help(args); return; }
 
-    #[clap(short, long)]
-    pub help: bool,                                 // -h or --help
+    // Use another instruction set if the arguments or the manifest say so
+    if let Some(isa) = args.isa.clone().or_else(|| read_manifest().isa) { use_instruction_set_dir(expand_path(isa.as_str()).unwrap()); }
 
-    #[clap(long)]
-    pub instruction_help: Option<Option<String>>,   // --instruction-help
+    if let Some(code) = args.explain.clone() { print_explanation(code); return; }
+    if args.instruction_help.is_some() { print_instruction_help(args.instruction_help.unwrap().unwrap()); return; }
 
-    #[clap(short, long)]
-    pub output_name: Option<Option<String>>,        // -o or --output
+    if args.list_instructions { print_instruction_list(); return; }
 
-    #[clap(short, long, num_args = 0..=1)]
-    pub get_micro_operation: Option<Option<String>>,// --get-micro-operation
+    if let Some(dirs) = args.diff_instruction_table.clone() { diff_instruction_tables(&dirs[0], &dirs[1]); return; }
 
-    #[clap(short, long)]
-    pub generate_instruction_table: bool,           // --generate-instructions-table
-}
+    if let Some(named) = args.opcode_map.clone() { print!("{}", op_code_map(&get_instruction_definitions(), max_op_code(), named.as_deref())); return; }
 
-impl ArgumentList{
-    pub fn new() -> ArgumentList{
-        ArgumentList{file: None, help: false, instruction_help: None, output_name: None, generate_instruction_table: false, get_micro_operation: None}
-    }
+    if let Some(named) = args.timing_chart.clone() { print_timing_chart(named, args.svg, args.output_dir); return; }
 
-    /// Checks whether the current amount of data is enough (0) or the file name is missing (1)
-    pub fn needs_input_file(&self) -> bool{
-        let is_ok = self.help || self.generate_instruction_table || self.file.is_some() || self.get_micro_operation.is_some() || self.instruction_help.is_some();
-        !is_ok
-    }
-}
+    if let Some(output_dir) = args.export_isa_docs.clone() { export_isa_docs(output_dir); return; }
 
-#[tokio::main]
-async fn main() {
-    // Generate a reasonable argument list
-    let mut args = ArgumentList::parse();
+    if args.generate_instruction_table { generate_instruction_table(args.output_dir, args.table_format.unwrap_or(TableFormat::Raw), args.combined); return; }
 
-    if args.help { print_help(args); return; }
+    if args.get_micro_operation.is_some() { get_micro_operation(args.get_micro_operation.unwrap().unwrap().to_string()); return;}
 
-    if args.instruction_help.is_some() { print_instruction_help(args.instruction_help.unwrap().unwrap()); return; }
+    if let Some(Command::Lib { action }) = args.command { run_lib_command(action).await; return; }
 
-    if args.generate_instruction_table { generate_instruction_table(); return; }
+    if args.command == Some(Command::Lsp) { run_language_server(); return; }
 
-    if args.get_micro_operation.is_some() { get_micro_operation(args.get_micro_operation.unwrap().unwrap().to_string()); return;}
+    if let Some(Command::Fmt { files, check }) = args.command { run_fmt(files, check); return; }
 
     // There is something to assemble
 
     // Load the instructions
-    let instructions = instruction::instruction::get_all_instructions();
+    let instructions = smiscasm::instruction::instruction::get_all_instructions();
 
     // Load the file
 
@@ -93,17 +93,84 @@ async fn main() {
 
 
 
-        let binary = assemble(input_file, instructions, relative_path.clone()).await;
+        let warnings = WarningOptions::parse(&args.warnings);
+
+        if let Err(message) = warnings.clone() {
+            exit(message, ExitCode::BadArgument);
+        }
+
+        let options = AssembleOptions { file_name: relative_path.clone(), fetch_libraries: true, warnings: warnings.unwrap(), check_registers: args.check_registers, progress: Some(|x| eprintln!("{}", x.to_string().blue())) };
+
+        let result = assemble(input_file, instructions, &DiskFileSystem, options).await;
+        let diagnostics = match &result { Ok(assembly) => &assembly.diagnostics, Err(error) => &error.diagnostics };
+
+        match args.message_format.unwrap_or(MessageFormat::Human) {
+            MessageFormat::Human => print!("{}", render_diagnostics(diagnostics)),
+            MessageFormat::Json => print!("{}", to_json_lines(diagnostics)),
+        }
+
+        if let Some(sarif_name) = args.sarif.clone() {
+            write_output_file(sarif_name, to_sarif(diagnostics).into_bytes());
+        }
+
+        let Ok(assembly) = result else { process::exit(105); };
+
+        // The control flow graph is written next to the input (main.s -> main.dot)
+        if let Some(CfgFormat::Dot) = args.emit_cfg {
+            let cfg_name = Path::new(args.file.as_ref().unwrap()).with_extension("dot").to_string_lossy().to_string();
+            write_output_file(cfg_name, to_dot(&assembly.cfg).into_bytes());
+        }
+
+        let (binary, sections) = (assembly.image, assembly.sections);
+
+        let format = args.format.unwrap_or(OutputFormat::Bin);
 
         // Generate the output file name in case it doesn't exist.
         if args.output_name.is_none(){
             args.output_name = Some(Some(args.file.clone().unwrap().to_string().clone().strip_suffix(".s").unwrap().to_string()));
-            args.output_name = Some(Some(args.output_name.unwrap().unwrap().clone() + ".o"));
+            args.output_name = Some(Some(args.output_name.unwrap().unwrap().clone() + format.file_suffix()));
+        }
+
+        let output_name = args.output_name.clone().unwrap().unwrap();
+
+        let mut base_address = 0u32;
+
+        if let Some(base_address_string) = args.base_address.clone() {
+            if let Some(address) = parse_address(base_address_string.as_str()) {
+                base_address = address;
+            } else {
+                exit(format!("String \"{}\" is not a valid base address.", base_address_string), ExitCode::BadArgument);
+            }
         }
 
-        let mut file = File::create(args.output_name.clone().unwrap().unwrap()).unwrap();
+        // The plain binary is written as it is (including the zero fill).
+        if format == OutputFormat::Bin && !args.split && base_address == 0 {
+            let mut file = File::create(output_name).unwrap();
 
-        file.write_all(binary.as_slice()).unwrap();
+            file.write_all(binary.as_slice()).unwrap();
+
+            return
+        }
+
+        let Some(segments) = segments_from_sections(&binary, &sections, base_address) else {
+            exit(format!("The binary ({}B) doesn't fit into the address space when it starts at base address 0x{:08x}.", binary.len(), base_address), ExitCode::BadArgument);
+            return
+        };
+
+        if !args.split {
+            write_output_file(output_name.clone(), encode(format, &segments, &output_name));
+            return
+        }
+
+        // One file per section, named like the output file with the section's name attached.
+        for segment in segments.iter() {
+            let section_output_name = match output_name.rsplit_once('.') {
+                Some((stem, suffix)) => format!("{}-{}.{}", stem, segment.name, suffix),
+                None => format!("{}-{}", output_name, segment.name),
+            };
+
+            write_output_file(section_output_name.clone(), encode(format, std::slice::from_ref(segment), &section_output_name));
+        }
 
         return
     }
@@ -111,6 +178,16 @@ async fn main() {
     println!("{}", "Nothing to do".to_string().red());
 }
 
+fn write_output_file(name: String, contents: Vec<u8>) {
+    let file = File::create(name.clone());
+
+    if file.is_err() {
+        exit(format!("Couldn't create output file '{}'.", name), ExitCode::ReadWriteError);
+    }
+
+    file.unwrap().write_all(contents.as_slice()).unwrap();
+}
+
 fn get_micro_operation(idx: String) {
     let idx_int = idx.parse::<usize>();
 
@@ -121,130 +198,75 @@ fn get_micro_operation(idx: String) {
     println!("That would be: {}", micro_operation_at(idx_int.unwrap()));
 }
 
-// This function is slow as fuck, I know that and I got ideas on how to solve it.
-// But it's not slow enough and doesn't take up too much RAM (on my machine at least; lol) for me to seriously care about it.
-fn generate_instruction_table() {
-    let instructions = instruction::instruction::get_all_instructions();
-    // All instructions' control words; position in vector counts as address/caller.
-    let mut all_control_words: Vec<u64> = vec![];
-
-    for i in 0..65536{
-        fn find_matching_stage(i: u16, instructions: Vec<Instruction>) -> Option<u64> {
-            for instruction in instructions.iter() {
-                for stage in instruction.stages.iter() {
-                    let caller = stage.0;
-
-                    if caller == i {
-                        return Some(stage.1);
-                    }
-                }
-            }
-            None
-        }
+/// Writes the CU's decoding tables, either as 8 byte-wide tables (one per EEPROM) or as one combined 64-bit-wide table.
+fn generate_instruction_table(output_dir: Option<String>, format: TableFormat, combined: bool) {
+    let instructions = smiscasm::instruction::instruction::get_all_instructions();
+    let control_words = generate_control_words(instructions, generate_empty_control_word());
 
-        // Look for the best match, then look if there are versions without the Z/PM flags set.
-        if let Some(matching_stage) = find_matching_stage(i as u16, instructions.clone()){
-            all_control_words.push(matching_stage);
-            continue;
-        }
+    let output_dir = expand_path(output_dir.unwrap_or(".".to_string()).as_str()).unwrap();
 
-        // Since there was no corresponding instruction found,
-        // i might be above 32768, in which case the MSb refers to the PM (privileged mode) flag.
-        // Some instructions only have one (privilege-independent) version tho.
-        // So this one might be used, too.
-        if let Some(matching_stage) = find_matching_stage((i as u16) & 0x7FFF, instructions.clone()){
-            all_control_words.push(matching_stage);
-            continue;
-        }
+    if fs::create_dir_all(&output_dir).is_err() {
+        exit(format!("Couldn't create output directory '{}'.", output_dir.display()), ExitCode::ReadWriteError);
+    }
 
-        // Since there was no corresponding instruction found,
-        // i might be above 16384, in which case the two MSBs (bits not bytes) refer to the PM flag and the ZF.
-        // Most instructions are ZF-independent, so ignore it and retry.
+    if combined {
+        let name = output_dir.join(table_file_name(None, format.file_suffix()));
+        write_output_file(name.to_str().unwrap().to_string(), encode_table(format, &control_words, 8));
+        return;
+    }
 
-        if let Some(matching_stage) = find_matching_stage((i as u16) & 0xBFFF, instructions.clone()){
-            all_control_words.push(matching_stage);
-            continue;
-        }
+    // The output is needed in 8 separate files for each EEPROM.
+    // File one represents the MSB (byte not bit) while file eight represents the LSB.
+    for (i, table) in split_control_words(&control_words).iter().enumerate() {
+        let name = output_dir.join(table_file_name(Some(i + 1), format.file_suffix()));
+        let values = table.iter().map(|&x| x as u64).collect::<Vec<u64>>();
 
-        // Same as above.
-        // Some instructions (if not most of them still) are both ZF and PM flag independent.
-        // Rerun ignoring both.
+        write_output_file(name.to_str().unwrap().to_string(), encode_table(format, &values, 1));
+    }
+}
 
-        if let Some(matching_stage) = find_matching_stage((i as u16) & 0x3FFF, instructions.clone()){
-            all_control_words.push(matching_stage);
-            continue;
-        }
+fn print_timing_chart(named: String, svg: bool, output_dir: Option<String>) {
+    let instructions = smiscasm::instruction::instruction::get_all_instructions();
+    let mut matching = find_instructions(&named, &instructions);
+    matching.sort_by_key(|x| x.op_code);
 
-        // No matching stage found, so write something with the MSb and only the MSb being a 1.
-        // This prevents damage that may arise from multiple buses outputting at once and is a clear signal.
-        // The MSb is the least likely to be used for anything, so this fits it perfectly.
-        // Thought about it, just push zero for now.
-        all_control_words.push(0x0);
+    if matching.is_empty() {
+        exit(format!("There is no instruction named {} (or with that op-code).", named), ExitCode::BadArgument);
     }
 
-    // Now store the list
-    let file1 = File::create("smiscasm_instructions-1-MSB.o");
-    let file2 = File::create("smiscasm_instructions-2.o");
-    let file3 = File::create("smiscasm_instructions-3.o");
-    let file4 = File::create("smiscasm_instructions-4.o");
-    let file5 = File::create("smiscasm_instructions-5.o");
-    let file6 = File::create("smiscasm_instructions-6.o");
-    let file7 = File::create("smiscasm_instructions-7.o");
-    let file8 = File::create("smiscasm_instructions-8-LSB.o");
-
-    if file1.is_err() || file2.is_err() || file3.is_err() || file4.is_err() || file5.is_err() || file6.is_err() || file7.is_err() || file8.is_err(){
-        exit("Couldn't create file 'smiscasm_instructions-x.o' to store instructions in.".to_string(), ExitCode::ReadWriteError);
-    }
+    let output_dir = expand_path(output_dir.unwrap_or(".".to_string()).as_str()).unwrap();
 
-    // Convert the list of u64 to a list of u8.
-    // The output is needed in 8 separate files for each EEPROM.
-    // File one represents the MSB (byte not bit) while file eight represents the LSB.
-    let mut control_words_u8: [[u8; 65536]; 8] = [[0; 65536]; 8];
-
-    for i in all_control_words.iter().enumerate() {
-        let control_word_u64 = *i.1;
-        let i = i.0;
-        let part_1 = ((control_word_u64 & 0xFF00000000000000) >> 56) as u8;
-        let part_2 = ((control_word_u64 & 0x00FF000000000000) >> 48) as u8;
-        let part_3 = ((control_word_u64 & 0x0000FF0000000000) >> 40) as u8;
-        let part_4 = ((control_word_u64 & 0x000000FF00000000) >> 32) as u8;
-        let part_5 = ((control_word_u64 & 0x00000000FF000000) >> 24) as u8;
-        let part_6 = ((control_word_u64 & 0x0000000000FF0000) >> 16) as u8;
-        let part_7 = ((control_word_u64 & 0x000000000000FF00) >> 8) as u8;
-        let part_8 =  (control_word_u64 & 0x00000000000000FF) as u8;
-
-        control_words_u8[0][i] = part_1;
-        control_words_u8[1][i] = part_2;
-        control_words_u8[2][i] = part_3;
-        control_words_u8[3][i] = part_4;
-        control_words_u8[4][i] = part_5;
-        control_words_u8[5][i] = part_6;
-        control_words_u8[6][i] = part_7;
-        control_words_u8[7][i] = part_8;
+    if svg && fs::create_dir_all(&output_dir).is_err() {
+        exit(format!("Couldn't create output directory '{}'.", output_dir.display()), ExitCode::ReadWriteError);
     }
 
+    for instruction in matching.iter() {
+        println!("{}", ascii_chart(instruction));
 
-    file1.unwrap().write_all(&control_words_u8[0]).unwrap();
-    file2.unwrap().write_all(&control_words_u8[1]).unwrap();
-    file3.unwrap().write_all(&control_words_u8[2]).unwrap();
-    file4.unwrap().write_all(&control_words_u8[3]).unwrap();
-    file5.unwrap().write_all(&control_words_u8[4]).unwrap();
-    file6.unwrap().write_all(&control_words_u8[5]).unwrap();
-    file7.unwrap().write_all(&control_words_u8[6]).unwrap();
-    file8.unwrap().write_all(&control_words_u8[7]).unwrap();
+        if svg {
+            let name = output_dir.join(format!("{}-{:02X}.svg", instruction.name, instruction.op_code));
+            write_output_file(name.to_str().unwrap().to_string(), svg_chart(instruction).into_bytes());
+        }
+    }
 }
 
+fn export_isa_docs(output_dir: String) {
+    let instructions = smiscasm::instruction::instruction::get_all_instructions();
+    let document = isa_document(&instructions, &ENCODING);
+
+    let output_dir = expand_path(output_dir.as_str()).unwrap();
+
+    if fs::create_dir_all(&output_dir).is_err() {
+        exit(format!("Couldn't create output directory '{}'.", output_dir.display()), ExitCode::ReadWriteError);
+    }
+
+    write_output_file(output_dir.join("isa.md").to_str().unwrap().to_string(), to_markdown(&document).into_bytes());
+    write_output_file(output_dir.join("isa.html").to_str().unwrap().to_string(), to_html(&document).into_bytes());
+}
 
-pub fn expand_path(path_str: &str) -> Option<PathBuf> {
-    let expanded = if path_str.starts_with("~/") {
-        let home = env::var("HOME").ok()?;
-        PathBuf::from(home).join(&path_str[2..])
-    } else if path_str.starts_with("$PWD/") {
-        let pwd = env::var("PWD").ok()?;
-        PathBuf::from(pwd).join(&path_str[5..])
-    } else {
-        PathBuf::from(path_str)
-    };
+fn diff_instruction_tables(old_dir: &str, new_dir: &str) {
+    let old = read_control_words(&expand_path(old_dir).unwrap());
+    let new = read_control_words(&expand_path(new_dir).unwrap());
 
-    Some(expanded)
+    print!("{}", diff_report(&old, &new));
 }
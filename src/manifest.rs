@@ -0,0 +1,64 @@
+// The project manifest stores settings that would otherwise have to be passed on every invocation.
+// It consists of "key value" lines, comments start with a '#'.
+
+use std::fs;
+use std::path::Path;
+use crate::config::MANIFEST_FILE_NAME;
+use crate::util::exit::{exit, ExitCode};
+use crate::util::remove_comments::remove_comments_in_line;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Manifest {
+    pub isa: Option<String>,        // The directory to load instructions from
+}
+
+/// Read the manifest from the working directory, or an empty one if there is none.
+pub fn read_manifest() -> Manifest {
+    if !Path::new(MANIFEST_FILE_NAME).exists() {
+        return Manifest::default();
+    }
+
+    let contents = fs::read_to_string(MANIFEST_FILE_NAME);
+
+    if contents.is_err() {
+        exit(format!("Couldn't read the manifest ({}).", MANIFEST_FILE_NAME), ExitCode::ReadWriteError);
+    }
+
+    parse_manifest(contents.unwrap().as_str())
+}
+
+pub fn parse_manifest(string: &str) -> Manifest {
+    let mut manifest = Manifest::default();
+
+    for line in string.lines() {
+        let line = remove_comments_in_line(line.to_string());
+        let line = line.trim();
+
+        if line.is_empty() { continue; }
+
+        let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let value = value.trim().trim_matches('"').to_string();
+
+        match key {
+            "isa" => manifest.isa = Some(value),
+
+            _ => {
+                exit(format!("Unknown key \"{}\" in the manifest ({}).", key, MANIFEST_FILE_NAME), ExitCode::BadArgument);
+            }
+        }
+    }
+
+    manifest
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::manifest::{parse_manifest, Manifest};
+
+    #[test]
+    fn test_parse_manifest() {
+        assert_eq!(parse_manifest("# Experimental CU\nisa \"../my isa\"\n"), Manifest { isa: Some("../my isa".to_string()) });
+        assert_eq!(parse_manifest(""), Manifest { isa: None });
+    }
+}
@@ -0,0 +1,158 @@
+// Machine-readable diagnostics for editors & CI: one JSON object per line or a SARIF 2.1.0 log.
+// Lines & columns are 1-based and refer to the file (columns in characters), like editors count them.
+
+use clap::ValueEnum;
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::{Diagnostic, Span};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum MessageFormat {
+    Human,      // Colored, with the affected lines (default)
+    Json,       // One JSON object per diagnostic and line
+}
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+fn severity_name(severity: &ErrorNotificationKind) -> &'static str {
+    match severity {
+        ErrorNotificationKind::Error => "error",
+        ErrorNotificationKind::Warning => "warning",
+    }
+}
+
+/// The column (1-based) the span starts at in the file, None if it isn't known.
+fn start_column(span: &Span) -> Option<u32> {
+    Some(span.indent + span.column? + 1)
+}
+
+fn json_string(string: &str) -> String {
+    let mut result = String::from("\"");
+
+    for char in string.chars() {
+        match char {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            x if (x as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", x as u32)),
+            x => result.push(x),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+fn json_number(number: Option<u32>) -> String {
+    number.map(|x| x.to_string()).unwrap_or("null".to_string())
+}
+
+fn json_span(span: &Span) -> String {
+    format!("\"file\":{},\"line\":{},\"column\":{},\"length\":{}", json_string(&span.file_name), span.line, json_number(start_column(span)), json_number(span.length))
+}
+
+/// A diagnostic as a single-line JSON object.
+/// {"severity", "code", "title", "message", "file", "line", "column", "length", "related": [{"file", "line", "column", "length", "label"}], "notes"}
+pub fn to_json(diagnostic: &Diagnostic) -> String {
+    let location = match &diagnostic.primary {
+        Some(span) => json_span(span),
+        None => "\"file\":null,\"line\":null,\"column\":null,\"length\":null".to_string(),
+    };
+
+    let related = diagnostic.secondary.iter().map(|(span, label)| format!("{{{},\"label\":{}}}", json_span(span), json_string(label))).collect::<Vec<String>>();
+    let notes = diagnostic.notes.iter().map(|x| json_string(x)).collect::<Vec<String>>();
+    let code = diagnostic.code.as_ref().map(|x| json_string(x)).unwrap_or("null".to_string());
+
+    format!("{{\"severity\":{},\"code\":{},\"title\":{},\"message\":{},{},\"related\":[{}],\"notes\":[{}]}}", json_string(severity_name(&diagnostic.severity)), code, json_string(&diagnostic.title), json_string(&diagnostic.message), location, related.join(","), notes.join(","))
+}
+
+/// One JSON object per diagnostic, each on its own line.
+pub fn to_json_lines(diagnostics: &[Diagnostic]) -> String {
+    diagnostics.iter().map(|x| format!("{}\n", to_json(x))).collect()
+}
+
+fn sarif_location(span: &Span, message: Option<&str>) -> String {
+    let mut region = format!("\"startLine\":{}", span.line);
+
+    if let Some(column) = start_column(span) {
+        region += &format!(",\"startColumn\":{}", column);
+
+        if let Some(length) = span.length {
+            region += &format!(",\"endColumn\":{}", column + length);
+        }
+    }
+
+    let message = message.map(|x| format!(",\"message\":{{\"text\":{}}}", json_string(x))).unwrap_or_default();
+
+    format!("{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}},\"region\":{{{}}}}}{}}}", json_string(&span.file_name), region, message)
+}
+
+/// A SARIF 2.1.0 log with one result per diagnostic (and one rule per code).
+pub fn to_sarif(diagnostics: &[Diagnostic]) -> String {
+    let mut codes: Vec<(&String, &String)> = Vec::new();
+
+    for diagnostic in diagnostics.iter() {
+        if let Some(code) = &diagnostic.code && !codes.iter().any(|x| x.0 == code) {
+            codes.push((code, &diagnostic.title));
+        }
+    }
+
+    let rules = codes.iter().map(|(code, title)| format!("        {{\"id\":{},\"shortDescription\":{{\"text\":{}}}}}", json_string(code), json_string(title))).collect::<Vec<String>>();
+
+    let results = diagnostics.iter().map(|diagnostic| {
+        let rule = diagnostic.code.as_ref().map(|x| format!("\"ruleId\":{},", json_string(x))).unwrap_or_default();
+        let locations = diagnostic.primary.iter().map(|x| sarif_location(x, None)).collect::<Vec<String>>();
+        let related = diagnostic.secondary.iter().map(|(span, label)| sarif_location(span, Some(label))).collect::<Vec<String>>();
+
+        let mut text = format!("{}: {}", diagnostic.title, diagnostic.message);
+        diagnostic.notes.iter().for_each(|x| text += &format!("\nNote: {}", x));
+
+        format!("        {{{}\"level\":{},\"message\":{{\"text\":{}}},\"locations\":[{}],\"relatedLocations\":[{}]}}", rule, json_string(severity_name(&diagnostic.severity)), json_string(&text), locations.join(","), related.join(","))
+    }).collect::<Vec<String>>();
+
+    let mut result = String::from("{\n");
+    result += &format!("  \"$schema\": {},\n  \"version\": \"2.1.0\",\n  \"runs\": [{{\n", json_string(SARIF_SCHEMA));
+    result += &format!("    \"tool\": {{\"driver\": {{\"name\": \"smiscasm\", \"version\": {}, \"rules\": [\n{}\n    ]}}}},\n", json_string(env!("CARGO_PKG_VERSION")), rules.join(",\n"));
+    result += &format!("    \"results\": [\n{}\n    ]\n", results.join(",\n"));
+    result += "  }]\n}\n";
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::output::diagnostics::{to_json, to_json_lines, to_sarif};
+    use crate::util::code_error::ErrorNotificationKind;
+    use crate::util::diagnostic::{Diagnostic, Span};
+
+    fn diagnostic() -> Diagnostic {
+        let span = Span { file_name: "main.s".to_string(), line: 4, indent: 4, column: Some(8), length: Some(7), code: "add x0, nothing".to_string() };
+        let mut diagnostic = Diagnostic::new(ErrorNotificationKind::Error, "Argument Decoding Error".to_string(), "Can't decode \"nothing\".".to_string(), Some(span));
+        diagnostic.notes.push("Check the spelling.".to_string());
+
+        diagnostic
+    }
+
+    #[test]
+    fn test_to_json() {
+        assert_eq!(to_json(&diagnostic()), "{\"severity\":\"error\",\"code\":\"E0013\",\"title\":\"Argument Decoding Error\",\"message\":\"Can't decode \\\"nothing\\\".\",\"file\":\"main.s\",\"line\":4,\"column\":13,\"length\":7,\"related\":[],\"notes\":[\"Check the spelling.\"]}");
+
+        let warning = Diagnostic::new(ErrorNotificationKind::Warning, "Stack Too Deep".to_string(), "a\nb".to_string(), None);
+        assert_eq!(to_json(&warning), "{\"severity\":\"warning\",\"code\":null,\"title\":\"Stack Too Deep\",\"message\":\"a\\nb\",\"file\":null,\"line\":null,\"column\":null,\"length\":null,\"related\":[],\"notes\":[]}");
+
+        assert_eq!(to_json_lines(&[diagnostic(), warning]).lines().count(), 2);
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let sarif = to_sarif(&[diagnostic(), diagnostic()]);
+
+        assert!(sarif.contains("\"version\": \"2.1.0\""));
+        assert_eq!(sarif.matches("{\"id\":\"E0013\"").count(), 1);
+        assert_eq!(sarif.matches("\"ruleId\":\"E0013\"").count(), 2);
+        assert!(sarif.contains("\"artifactLocation\":{\"uri\":\"main.s\"},\"region\":{\"startLine\":4,\"startColumn\":13,\"endColumn\":20}"));
+        assert!(sarif.contains("\"text\":\"Argument Decoding Error: Can't decode \\\"nothing\\\".\\nNote: Check the spelling.\""));
+    }
+}
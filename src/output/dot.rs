@@ -0,0 +1,119 @@
+// Control flow graphs as Graphviz DOT (`--emit-cfg dot`): one box per basic block, one cluster per section.
+
+use clap::ValueEnum;
+use crate::assembler::cfg::{ControlFlowGraph, EdgeKind, RunsInto};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum CfgFormat {
+    Dot,        // Graphviz
+}
+
+/// Make text usable in a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The first node of each basic block: entries, labels, targets and whatever comes after instructions that don't just continue.
+fn block_starts(graph: &ControlFlowGraph) -> Vec<usize> {
+    let mut starts = graph.entries.clone();
+
+    for (i, node) in graph.nodes.iter().enumerate() {
+        if i == 0 || !node.labels.is_empty() { starts.push(i); }
+
+        starts.extend(node.successors.iter().filter(|x| x.kind != EdgeKind::Next).map(|x| x.to));
+
+        let only_continues = node.successors.len() == 1 && node.successors[0].kind == EdgeKind::Next && !node.unresolved;
+        if !only_continues && i + 1 < graph.nodes.len() { starts.push(i + 1); }
+    }
+
+    starts.sort();
+    starts.dedup();
+    starts
+}
+
+/// The graph in DOT. Unreachable blocks are grey, jumps that can't be followed lead to "?".
+pub fn to_dot(graph: &ControlFlowGraph) -> String {
+    let starts = block_starts(graph);
+    let block_of = |node: usize| starts.iter().rposition(|x| *x <= node).unwrap_or(0);
+
+    let mut result = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+    let mut sections: Vec<&String> = Vec::new();
+    let mut edges = Vec::new();
+    let mut has_unknown = false;
+
+    for section in graph.nodes.iter().map(|x| &x.section) {
+        if !sections.contains(&section) { sections.push(section); }
+    }
+
+    for (section_index, section) in sections.iter().enumerate() {
+        result += &format!("    subgraph cluster_{} {{\n        label=\"{}\";\n", section_index, escape(section));
+
+        for (block, start) in starts.iter().enumerate() {
+            if graph.nodes[*start].section != **section { continue; }
+
+            let end = starts.get(block + 1).copied().unwrap_or(graph.nodes.len());
+            let mut label = String::new();
+
+            for node in graph.nodes[*start..end].iter() {
+                for name in node.labels.iter() { label += &format!("{}:\\l", escape(name)); }
+                label += &format!("0x{:04x}  {}\\l", node.address, escape(&node.code));
+            }
+
+            let style = if graph.nodes[*start].reachable { "" } else { ", style=dashed, color=grey, fontcolor=grey" };
+            result += &format!("        b{} [label=\"{}\"{}];\n", block, label, style);
+
+            // The block's edges leave from its last instruction
+            let last = &graph.nodes[end - 1];
+
+            for edge in last.successors.iter() {
+                let attributes = match edge.kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [style=bold]",
+                    EdgeKind::Branch => " [label=\"taken\"]",
+                    EdgeKind::Call => " [style=dashed, label=\"call\"]",
+                };
+
+                edges.push(format!("    b{} -> b{}{};\n", block, block_of(edge.to), attributes));
+            }
+
+            if last.unresolved {
+                has_unknown = true;
+                edges.push(format!("    b{} -> unknown [style=dotted];\n", block));
+            }
+
+            match last.runs_into {
+                Some(RunsInto::Data) => edges.push(format!("    b{} -> data [color=red];\n", block)),
+                Some(RunsInto::SectionEnd) => edges.push(format!("    b{} -> section_end [color=red];\n", block)),
+                None => {}
+            }
+        }
+
+        result += "    }\n";
+    }
+
+    if has_unknown { result += "    unknown [label=\"?\", shape=circle];\n"; }
+    if graph.nodes.iter().any(|x| x.runs_into == Some(RunsInto::Data)) { result += "    data [label=\"data\", shape=plaintext, fontcolor=red];\n"; }
+    if graph.nodes.iter().any(|x| x.runs_into == Some(RunsInto::SectionEnd)) { result += "    section_end [label=\"end of section\", shape=plaintext, fontcolor=red];\n"; }
+
+    for edge in edges { result += &edge; }
+    result += "}\n";
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::assembler::{assemble, AssembleOptions};
+    use crate::instruction::instruction::get_all_instructions;
+    use crate::output::dot::to_dot;
+    use crate::util::file_system::MemoryFileSystem;
+
+    #[tokio::test]
+    async fn test_to_dot() {
+        let code = ".section \"CODE\"\n.mode text\n_main:\n    adrp x17, _loop@PAGE\n    add x17, _loop@PAGEOFF\n_loop:\n    sub x1, 1\n    jmpz x1, x0\n    jmp x17\n";
+        let assembled = assemble(code.to_string(), get_all_instructions(), &MemoryFileSystem::new(), AssembleOptions::new("main.s".to_string())).await.unwrap();
+
+        assert_eq!(to_dot(&assembled.cfg), "digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n    subgraph cluster_0 {\n        label=\"CODE\";\n        b0 [label=\"_main:\\l0x0000  adrp x17, _loop@PAGE\\l0x0004  add x17, _loop@PAGEOFF\\l\"];\n        b1 [label=\"_loop:\\l0x0008  sub x1, 1\\l0x000c  jmpz x1, x0\\l\"];\n        b2 [label=\"0x0010  jmp x17\\l\"];\n    }\n    unknown [label=\"?\", shape=circle];\n    b0 -> b1;\n    b1 -> b2;\n    b1 -> unknown [style=dotted];\n    b2 -> b1 [style=bold];\n}\n");
+    }
+}
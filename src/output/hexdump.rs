@@ -0,0 +1,39 @@
+// A hex dump for humans, similar to `hexdump -C`
+
+use crate::output::output::Segment;
+
+const BYTES_PER_LINE: usize = 16;
+
+pub fn encode(segments: &[Segment]) -> String {
+    let mut result = String::new();
+
+    for segment in segments.iter() {
+        result += format!("# Section \"{}\" ({} bytes)\n", segment.name, segment.data.len()).as_str();
+
+        for (i, chunk) in segment.data.chunks(BYTES_PER_LINE).enumerate() {
+            let address = segment.address + (i * BYTES_PER_LINE) as u32;
+            let bytes = chunk.iter().map(|x| format!("{:02x}", x)).collect::<Vec<String>>().join(" ");
+            let text = chunk.iter().map(|&x| if x.is_ascii_graphic() || x == b' ' { x as char } else { '.' }).collect::<String>();
+
+            result += format!("{:08x}  {:<47}  |{}|\n", address, bytes, text).as_str();
+        }
+
+        result += "\n";
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::output::hexdump::encode;
+    use crate::output::output::Segment;
+
+    #[test]
+    fn test_encode() {
+        let segments = vec![Segment { name: "DATA".to_string(), address: 0x1000, data: "SMISC".as_bytes().to_vec() }];
+
+        assert_eq!(encode(&segments), "# Section \"DATA\" (5 bytes)\n00001000  53 4d 49 53 43                                   |SMISC|\n\n");
+    }
+}
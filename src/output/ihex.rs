@@ -0,0 +1,70 @@
+// Intel HEX (https://en.wikipedia.org/wiki/Intel_HEX)
+
+use crate::output::output::Segment;
+
+/// The amount of data bytes per record
+const BYTES_PER_RECORD: usize = 16;
+
+pub fn encode(segments: &[Segment]) -> String {
+    let mut result = String::new();
+    let mut current_upper_address: Option<u16> = None;
+
+    for segment in segments.iter() {
+        let mut offset = 0usize;
+
+        while offset < segment.data.len() {
+            let address = segment.address + offset as u32;
+            let upper_address = (address >> 16) as u16;
+
+            // Addresses above 64KiB need an extended linear address record first.
+            if current_upper_address != Some(upper_address) && (upper_address != 0 || current_upper_address.is_some()) {
+                result += record(0, 0x04, &upper_address.to_be_bytes()).as_str();
+            }
+            current_upper_address = Some(upper_address);
+
+            // Records mustn't cross a 64KiB boundary
+            let bytes_until_boundary = 0x10000 - (address & 0xFFFF) as usize;
+            let length = BYTES_PER_RECORD.min(segment.data.len() - offset).min(bytes_until_boundary);
+
+            result += record(address as u16, 0x00, &segment.data[offset..offset + length]).as_str();
+            offset += length;
+        }
+    }
+
+    // End of file
+    result += record(0, 0x01, &[]).as_str();
+
+    result
+}
+
+fn record(address: u16, record_type: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(record_type);
+    bytes.extend_from_slice(data);
+
+    // Two's complement of the sum of all bytes
+    let checksum = bytes.iter().fold(0u8, |sum, x| sum.wrapping_add(*x)).wrapping_neg();
+    bytes.push(checksum);
+
+    format!(":{}\n", bytes.iter().map(|x| format!("{:02X}", x)).collect::<Vec<String>>().join(""))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::output::ihex::encode;
+    use crate::output::output::Segment;
+
+    #[test]
+    fn test_encode() {
+        let segments = vec![Segment { name: "DATA".to_string(), address: 0x0030, data: vec![0x02, 0x33, 0x7A] }];
+
+        assert_eq!(encode(&segments), ":0300300002337A1E\n:00000001FF\n");
+
+        // Crossing 64KiB needs an extended linear address record
+        let segments = vec![Segment { name: "CODE".to_string(), address: 0xFFFF, data: vec![0x01, 0x02] }];
+
+        assert_eq!(encode(&segments), ":01FFFF000100\n:020000040001F9\n:0100000002FD\n:00000001FF\n");
+    }
+}
@@ -0,0 +1,280 @@
+// Renders the instruction set as a reference document (Markdown and HTML) for people building or learning the hardware.
+
+use crate::help::reference::{executed_stages, instruction_summary};
+use crate::instruction::encoding::{Encoding, INSTRUCTION_BITS};
+use crate::instruction::call_word::flags_description;
+use crate::instruction::instruction::Instruction;
+use crate::instruction::micro_operation::{BusRole, MICRO_OPERATIONS};
+
+/// The README, which has the register conventions
+const README_STRING: &str = include_str!("../../README.md");
+
+/// The parts documents are made of, so the same document can be written as Markdown and HTML.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Heading(u8, String),
+    Paragraph(String),
+    Table(Vec<String>, Vec<Vec<String>>),   // Header & rows
+}
+
+/// The whole reference document for the instructions.
+pub fn isa_document(instructions: &[Instruction], encoding: &Encoding) -> Vec<Block> {
+    let mut sorted = instructions.iter().collect::<Vec<&Instruction>>();
+    sorted.sort_by(|a, b| a.name.cmp(&b.name).then(a.op_code.cmp(&b.op_code)));
+
+    let mut blocks = vec![
+        Block::Heading(1, "SMISC Instruction Set".to_string()),
+        Block::Paragraph(format!("Generated by smiscasm from the instruction definitions ({} instructions). Don't edit this by hand, regenerate it with --export-isa-docs instead.", sorted.len())),
+    ];
+
+    blocks.push(Block::Heading(2, "Encoding".to_string()));
+    blocks.push(Block::Paragraph(format!("Instructions are {} bits wide. Register operands use the register fields in order, immediate values are placed in the immediate field (the first one at its LSb). Registers may share bits with the immediate field.", INSTRUCTION_BITS)));
+
+    let mut fields = vec![vec!["Op-code".to_string(), bit_range(encoding.op_code.shift, encoding.op_code.bits)]];
+    fields.extend(encoding.registers.iter().enumerate().map(|(i, x)| vec![format!("Register {}", i + 1), bit_range(x.shift, x.bits)]));
+    fields.push(vec!["Immediate".to_string(), bit_range(encoding.immediates.shift, encoding.immediates.bits)]);
+
+    blocks.push(Block::Table(vec!["Field".to_string(), "Bits".to_string()], fields));
+
+    blocks.push(Block::Heading(2, "Registers".to_string()));
+    if let Some((header, rows)) = register_table() { blocks.push(Block::Table(header, rows)); }
+
+    blocks.push(Block::Heading(2, "Micro Operations".to_string()));
+    blocks.push(Block::Table(
+        vec!["Name".to_string(), "Bit".to_string(), "Active".to_string(), "Bus".to_string()],
+        MICRO_OPERATIONS.iter().map(|x| vec![
+            x.name.clone(),
+            x.pos_in_control_word.to_string(),
+            if x.is_active_low { "low" } else { "high" }.to_string(),
+            match x.role { BusRole::Driver => "drives", BusRole::Reader => "reads", BusRole::Control => "-" }.to_string(),
+        ]).collect(),
+    ));
+
+    blocks.push(Block::Heading(2, "Instructions".to_string()));
+
+    let mut names = sorted.iter().map(|x| x.name.clone()).collect::<Vec<String>>();
+    names.dedup();
+
+    for name in names.iter() {
+        blocks.push(Block::Heading(3, name.clone()));
+
+        if let Some(summary) = instruction_summary(name) { blocks.push(Block::Paragraph(summary)); }
+
+        for instruction in sorted.iter().filter(|x| &x.name == name) {
+            blocks.extend(instruction_blocks(instruction, encoding));
+        }
+    }
+
+    blocks
+}
+
+/// The format, op-code, encoding & timing diagrams of an instruction.
+fn instruction_blocks(instruction: &Instruction, encoding: &Encoding) -> Vec<Block> {
+    let usage = format!("{} {}", instruction.name, instruction.format_notation());
+    let kinds = instruction.format.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+
+    let mut blocks = vec![
+        Block::Heading(4, usage.trim_end().to_string()),
+        Block::Paragraph(format!("Op-code 0x{:02X}, operands: {}.", instruction.op_code, if kinds.is_empty() { "none".to_string() } else { kinds.join(", ") })),
+    ];
+
+    let (header, row) = encoding_diagram(instruction, encoding);
+    blocks.push(Block::Table(header, vec![row]));
+
+    for flags in instruction.flag_versions() {
+        let (header, rows) = timing_diagram(instruction, flags);
+
+        blocks.push(Block::Paragraph(format!("Timing for {} ({} stages):", flags_description(flags), header.len() - 1)));
+        blocks.push(Block::Table(header, rows));
+    }
+
+    blocks
+}
+
+/// The instruction's bits from the MSb to the LSb: a header with the bit ranges and a row with what's in them.
+pub fn encoding_diagram(instruction: &Instruction, encoding: &Encoding) -> (Vec<String>, Vec<String>) {
+    let mut fields = vec![(encoding.op_code.shift, encoding.op_code.bits, format!("op-code (0x{:02X})", instruction.op_code))];
+
+    if let Some(shifts) = encoding.operand_shifts(&instruction.format) {
+        for (i, (kind, shift)) in instruction.format.iter().zip(shifts.iter()).enumerate() {
+            fields.push((*shift, kind.bits(), format!("operand {} ({})", i + 1, kind.notation())));
+        }
+    }
+
+    fields.sort_by_key(|x| std::cmp::Reverse(x.0));
+
+    // Fill the gaps with unused bits
+    let mut header: Vec<String> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut next_bit = INSTRUCTION_BITS;
+
+    for (shift, bits, name) in fields.iter() {
+        if shift + bits < next_bit {
+            header.push(bit_range(shift + bits, next_bit - shift - bits));
+            row.push("0".to_string());
+        }
+
+        header.push(bit_range(*shift, *bits));
+        row.push(name.clone());
+        next_bit = *shift;
+    }
+
+    if next_bit > 0 {
+        header.push(bit_range(0, next_bit));
+        row.push("0".to_string());
+    }
+
+    (header, row)
+}
+
+/// Which micro operations are active in which stage (columns) with the given flags set: a header & one row per micro operation used.
+pub fn timing_diagram(instruction: &Instruction, flags: u16) -> (Vec<String>, Vec<Vec<String>>) {
+    let stages = executed_stages(instruction, flags);
+
+    let mut header = vec!["Micro Operation".to_string()];
+    header.extend((0..stages.len()).map(|x| x.to_string()));
+
+    let rows = MICRO_OPERATIONS.iter()
+        .filter(|x| stages.iter().any(|y| y.contains(&x.name)))
+        .map(|x| {
+            let mut row = vec![x.name.clone()];
+            row.extend(stages.iter().map(|y| if y.contains(&x.name) { "X" } else { "" }.to_string()));
+            row
+        })
+        .collect();
+
+    (header, rows)
+}
+
+/// The register table from the README.
+pub fn register_table() -> Option<(Vec<String>, Vec<Vec<String>>)> {
+    let mut lines = README_STRING.lines()
+        .skip_while(|x| x.trim() != "### Registers")
+        .skip(1)
+        .take_while(|x| !x.starts_with('#'))
+        .filter(|x| x.trim_start().starts_with('|'))
+        .map(|x| x.trim().trim_matches('|').split('|').map(|y| y.trim().to_string()).collect::<Vec<String>>());
+
+    let header = lines.next()?;
+
+    // The second line is the |---|---| separator
+    Some((header, lines.skip(1).collect()))
+}
+
+/// "31-23" for a multi-bit field, "5" for a single bit.
+fn bit_range(shift: u8, bits: u8) -> String {
+    if bits == 1 { shift.to_string() } else { format!("{}-{}", shift + bits - 1, shift) }
+}
+
+pub fn to_markdown(blocks: &[Block]) -> String {
+    let escape = |x: &String| x.replace('|', "\\|");
+    let mut result = String::new();
+
+    for block in blocks.iter() {
+        match block {
+            Block::Heading(level, text) => result.push_str(&format!("{} {}\n\n", "#".repeat(*level as usize), text)),
+            Block::Paragraph(text) => result.push_str(&format!("{}\n\n", text)),
+            Block::Table(header, rows) => {
+                result.push_str(&format!("| {} |\n", header.iter().map(escape).collect::<Vec<String>>().join(" | ")));
+                result.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+
+                for row in rows.iter() {
+                    result.push_str(&format!("| {} |\n", row.iter().map(escape).collect::<Vec<String>>().join(" | ")));
+                }
+
+                result.push('\n');
+            }
+        }
+    }
+
+    result
+}
+
+pub fn to_html(blocks: &[Block]) -> String {
+    let escape = |x: &String| x.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;");
+    let title = blocks.iter().find_map(|x| if let Block::Heading(1, text) = x { Some(escape(text)) } else { None }).unwrap_or_default();
+
+    let mut result = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\nbody {{ font-family: sans-serif; max-width: 60em; margin: auto; }}\ntable {{ border-collapse: collapse; margin-bottom: 1em; }}\nth, td {{ border: 1px solid #aaa; padding: 0.2em 0.5em; text-align: center; }}\ntd:first-child {{ text-align: left; }}\n</style>\n</head>\n<body>\n", title);
+
+    for block in blocks.iter() {
+        match block {
+            Block::Heading(level, text) => result.push_str(&format!("<h{0}>{1}</h{0}>\n", level, escape(text))),
+            Block::Paragraph(text) => result.push_str(&format!("<p>{}</p>\n", escape(text))),
+            Block::Table(header, rows) => {
+                result.push_str("<table>\n<tr>");
+                header.iter().for_each(|x| result.push_str(&format!("<th>{}</th>", escape(x))));
+                result.push_str("</tr>\n");
+
+                for row in rows.iter() {
+                    result.push_str("<tr>");
+                    row.iter().for_each(|x| result.push_str(&format!("<td>{}</td>", escape(x))));
+                    result.push_str("</tr>\n");
+                }
+
+                result.push_str("</table>\n");
+            }
+        }
+    }
+
+    result.push_str("</body>\n</html>\n");
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::encoding::{parse_encoding, ENCODING};
+    use crate::instruction::instruction::{get_all_instructions, Instruction};
+    use crate::output::isa_docs::{encoding_diagram, isa_document, register_table, timing_diagram, to_html, to_markdown, Block};
+
+    #[test]
+    fn test_encoding_diagram() {
+        let encoding = parse_encoding("@FIELD opcode 23 9\n@FIELD reg 18 5\n@FIELD reg 13 5\n@FIELD imm 0 23");
+        let instruction = Instruction::new_with_op("add".to_string(), vec![], 0xA0, vec![]);
+        assert_eq!(encoding_diagram(&instruction, &encoding), (vec!["31-23".to_string(), "22-0".to_string()], vec!["op-code (0xA0)".to_string(), "0".to_string()]));
+
+        let instruction = Instruction::from_string("add\nx i\nA0\n@STAGE\nRESET_MICRO".to_string());
+        let (header, row) = encoding_diagram(&instruction, &encoding);
+
+        assert_eq!(header, vec!["31-23", "22-18", "17-13", "12-0"]);
+        assert_eq!(row, vec!["op-code (0xA0)", "operand 1 (x)", "0", "operand 2 (i)"]);
+    }
+
+    #[test]
+    fn test_timing_diagram() {
+        let instruction = Instruction::from_string("a\n\n01\n@STAGE\nPC_OUT\nMEM_ADDR_PTR_IN\n@STAGE\nPC_OUT\nRESET_MICRO".to_string());
+        let (header, rows) = timing_diagram(&instruction, 0);
+
+        assert_eq!(header, vec!["Micro Operation", "0", "1", "2"]);
+        assert!(rows.contains(&vec!["PC_OUT".to_string(), "".to_string(), "X".to_string(), "X".to_string()]));
+        assert!(rows.contains(&vec!["MEM_ADDR_PTR_IN".to_string(), "".to_string(), "X".to_string(), "".to_string()]));
+        assert_eq!(rows.len(), 3);
+    }
+
+    #[test]
+    fn test_register_table() {
+        let (header, rows) = register_table().unwrap();
+
+        assert_eq!(header, vec!["NAME(S)", "Description", "Info", "VM-only"]);
+        assert_eq!(rows[0], vec!["x0", "Normal", "Argument, return value", "No"]);
+        assert!(rows.iter().any(|x| x[0] == "x31/sp"));
+    }
+
+    #[test]
+    fn test_rendering() {
+        let blocks = vec![Block::Heading(1, "A <b>".to_string()), Block::Table(vec!["a|b".to_string()], vec![vec!["1".to_string()]])];
+
+        assert_eq!(to_markdown(&blocks), "# A <b>\n\n| a\\|b |\n|---|\n| 1 |\n\n");
+        assert!(to_html(&blocks).contains("<h1>A &lt;b&gt;</h1>\n<table>\n<tr><th>a|b</th></tr>\n<tr><td>1</td></tr>\n</table>"));
+    }
+
+    #[test]
+    fn test_isa_document() {
+        let instructions = get_all_instructions();
+        let markdown = to_markdown(&isa_document(&instructions, &ENCODING));
+
+        assert!(markdown.contains("#### add x i\n\nOp-code 0xA0"));
+        assert!(markdown.contains("### nand"));
+        assert!(markdown.contains("| x31/sp |"));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod output;
+pub mod ihex;
+pub mod srec;
+pub mod hexdump;
+pub mod table;
+pub mod isa_docs;
+pub mod timing;
+pub mod diagnostics;
+pub mod dot;
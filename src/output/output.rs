@@ -0,0 +1,125 @@
+// Writes assembled binaries in the formats EEPROM/flash programmers (and humans) understand.
+
+use clap::ValueEnum;
+use crate::assembler::zstep::PlacedSection;
+use crate::output::{hexdump, ihex, srec};
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Bin,        // The raw binary, including the zero fill between sections
+    Ihex,       // Intel HEX
+    Srec,       // Motorola S-record
+    Hexdump,    // Human-readable hex dump
+}
+
+impl OutputFormat {
+    /// The suffix (including the dot) output files get if no name has been specified.
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            OutputFormat::Bin => ".o",
+            OutputFormat::Ihex => ".hex",
+            OutputFormat::Srec => ".srec",
+            OutputFormat::Hexdump => ".txt",
+        }
+    }
+}
+
+/// A continuous range of bytes at an address.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Segment {
+    pub name: String,
+    pub address: u32,
+    pub data: Vec<u8>,
+}
+
+/// Cut the binary into one segment per section, leaving out the zero fill between them.
+/// None if a section doesn't fit below 2^32 when the binary starts at the base address.
+pub fn segments_from_sections(binary: &[u8], sections: &[PlacedSection], base_address: u32) -> Option<Vec<Segment>> {
+    sections.iter()
+        .filter(|x| x.length > 0)
+        .map(|x| {
+            let address = u32::try_from(x.start).ok().and_then(|start| base_address.checked_add(start))?;
+
+            // The last byte has to have an address, too
+            u32::try_from(x.length - 1).ok().and_then(|length| address.checked_add(length))?;
+
+            Some(Segment { name: x.name.clone(), address, data: binary[x.start..x.start + x.length].to_vec() })
+        })
+        .collect()
+}
+
+/// Encode segments in the given format.
+/// Raw binaries can't skip the gaps between segments, so those are filled with zeros starting at the first segment.
+pub fn encode(format: OutputFormat, segments: &[Segment], file_name: &str) -> Vec<u8> {
+    match format {
+        OutputFormat::Bin => {
+            let mut result: Vec<u8> = Vec::new();
+            let start = segments.first().map(|x| x.address).unwrap_or(0);
+
+            for segment in segments.iter() {
+                let offset = (segment.address - start) as usize;
+                result.resize(offset, 0);
+                result.extend_from_slice(&segment.data);
+            }
+
+            result
+        }
+        OutputFormat::Ihex => ihex::encode(segments).into_bytes(),
+        OutputFormat::Srec => srec::encode(segments, file_name).into_bytes(),
+        OutputFormat::Hexdump => hexdump::encode(segments).into_bytes(),
+    }
+}
+
+/// Parse an address like 4096, 0x1000, 0o10000 or 0b1000000000000.
+pub fn parse_address(string: &str) -> Option<u32> {
+    if let Some(hex) = string.strip_prefix("0x") { return u32::from_str_radix(hex, 16).ok(); }
+    if let Some(octal) = string.strip_prefix("0o") { return u32::from_str_radix(octal, 8).ok(); }
+    if let Some(binary) = string.strip_prefix("0b") { return u32::from_str_radix(binary, 2).ok(); }
+
+    string.parse::<u32>().ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::assembler::zstep::PlacedSection;
+    use crate::output::output::{encode, parse_address, segments_from_sections, OutputFormat, Segment};
+
+    #[test]
+    fn test_segments_from_sections() {
+        let mut binary = vec![0u8; 4096 + 2];
+        binary[0] = 1;
+        binary[4096] = 2;
+        binary[4097] = 3;
+
+        let sections = vec![
+            PlacedSection { name: "CODE".to_string(), start: 0, length: 4 },
+            PlacedSection { name: "DATA".to_string(), start: 4096, length: 2 },
+        ];
+
+        let segments = segments_from_sections(&binary, &sections, 0x8000).unwrap();
+
+        assert_eq!(segments, vec![
+            Segment { name: "CODE".to_string(), address: 0x8000, data: vec![1, 0, 0, 0] },
+            Segment { name: "DATA".to_string(), address: 0x9000, data: vec![2, 3] },
+        ]);
+
+        // Only single sections are written when splitting, so there mustn't be any leading zeros.
+        assert_eq!(encode(OutputFormat::Bin, &segments[1..], ""), vec![2, 3]);
+        assert_eq!(encode(OutputFormat::Bin, &segments, "").len(), 4096 + 2);
+
+        // The image has to end below 2^32
+        assert_eq!(segments_from_sections(&binary, &sections, 0xFFFFFFFF - 4097).unwrap()[1].address, 0xFFFFFFFE);
+        assert_eq!(segments_from_sections(&binary, &sections, 0xFFFFFFFF - 4096), None);
+        assert_eq!(segments_from_sections(&binary, &sections, 0xFFFFFFFF), None);
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("4096"), Some(4096));
+        assert_eq!(parse_address("0x1000"), Some(4096));
+        assert_eq!(parse_address("0o10"), Some(8));
+        assert_eq!(parse_address("0b101"), Some(5));
+        assert_eq!(parse_address("page"), None);
+    }
+}
@@ -0,0 +1,83 @@
+// Motorola S-record (https://en.wikipedia.org/wiki/SREC_(file_format))
+
+use crate::output::output::Segment;
+
+/// The amount of data bytes per record
+const BYTES_PER_RECORD: usize = 16;
+
+/// The most header bytes that fit into the S0 record (its length byte also counts the address & the checksum)
+const MAX_HEADER_LENGTH: usize = 255 - 2 - 1;
+
+pub fn encode(segments: &[Segment], header: &str) -> String {
+    let mut result = String::new();
+
+    // The address width depends on the highest address used.
+    let highest_address = segments.iter().map(|x| x.address as u64 + x.data.len() as u64).max().unwrap_or(0);
+    let address_length = match highest_address {
+        0..=0x10000 => 2,
+        0x10001..=0x1000000 => 3,
+        _ => 4,
+    };
+
+    let header = &header.as_bytes()[..header.len().min(MAX_HEADER_LENGTH)];
+    result += record(0, &0u32.to_be_bytes()[2..], header).as_str();
+
+    let mut data_records_count = 0u32;
+
+    for segment in segments.iter() {
+        for (i, chunk) in segment.data.chunks(BYTES_PER_RECORD).enumerate() {
+            let address = u32::try_from(i * BYTES_PER_RECORD).ok().and_then(|x| segment.address.checked_add(x)).expect("Segments end below 2^32");
+            result += record(address_length - 1, &address.to_be_bytes()[4 - address_length as usize..], chunk).as_str();
+            data_records_count += 1;
+        }
+    }
+
+    // Record count (S5 for 16 bit, S6 for 24 bit counts)
+    if data_records_count <= 0xFFFF {
+        result += record(5, &data_records_count.to_be_bytes()[2..], &[]).as_str();
+    } else if data_records_count <= 0xFFFFFF {
+        result += record(6, &data_records_count.to_be_bytes()[1..], &[]).as_str();
+    }
+
+    // Termination with the start address (S9, S8 & S7 belong to S1, S2 & S3)
+    let start_address = segments.first().map(|x| x.address).unwrap_or(0);
+    result += record(11 - address_length, &start_address.to_be_bytes()[4 - address_length as usize..], &[]).as_str();
+
+    result
+}
+
+fn record(record_type: u8, address: &[u8], data: &[u8]) -> String {
+    let mut bytes = vec![(address.len() + data.len() + 1) as u8];
+    bytes.extend_from_slice(address);
+    bytes.extend_from_slice(data);
+
+    // Ones' complement of the sum of all bytes
+    let checksum = !bytes.iter().fold(0u8, |sum, x| sum.wrapping_add(*x));
+    bytes.push(checksum);
+
+    format!("S{}{}\n", record_type, bytes.iter().map(|x| format!("{:02X}", x)).collect::<Vec<String>>().join(""))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::output::output::Segment;
+    use crate::output::srec::encode;
+
+    #[test]
+    fn test_encode() {
+        let segments = vec![Segment { name: "CODE".to_string(), address: 0x0000, data: vec![0x28, 0x5F, 0x24, 0x5F, 0x22, 0x12, 0x22, 0x6A, 0x00, 0x04, 0x24, 0x29, 0x00, 0x08, 0x23, 0x7C] }];
+
+        assert_eq!(encode(&segments, "HDR"), "S00600004844521B\nS1130000285F245F2212226A000424290008237C2A\nS5030001FB\nS9030000FC\n");
+
+        // Addresses above 64KiB need S2 records
+        let segments = vec![Segment { name: "DATA".to_string(), address: 0x10000, data: vec![0xFF] }];
+
+        assert_eq!(encode(&segments, ""), "S0030000FC\nS205010000FFFA\nS5030001FB\nS804010000FA\n");
+
+        // Long headers are cut to fit into one record
+        let header = encode(&segments, &"h".repeat(300)).lines().next().unwrap().to_string();
+        assert_eq!(&header[..4], "S0FF");
+        assert_eq!(header.len(), 2 + 2 * 256);
+    }
+}
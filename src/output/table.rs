@@ -0,0 +1,86 @@
+// Writes the control unit's decoding tables for EEPROM programmers and HDL models.
+
+use clap::ValueEnum;
+use crate::output::ihex;
+use crate::output::output::Segment;
+
+/// The amount of values per line in Logisim images
+const VALUES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum TableFormat {
+    Raw,        // The raw bytes (default)
+    Ihex,       // Intel HEX
+    Logisim,    // Logisim(-evolution) memory image ("v2.0 raw")
+    Readmemh,   // Verilog $readmemh
+}
+
+impl TableFormat {
+    /// The suffix (including the dot) the table files get.
+    pub fn file_suffix(&self) -> &'static str {
+        match self {
+            TableFormat::Raw => ".o",
+            TableFormat::Ihex => ".hex",
+            TableFormat::Logisim => ".img",
+            TableFormat::Readmemh => ".mem",
+        }
+    }
+}
+
+/// Encode a table whose entries are `width` bytes wide (1 for single EEPROMs, 8 for the whole control word).
+pub fn encode_table(format: TableFormat, values: &[u64], width: usize) -> Vec<u8> {
+    match format {
+        TableFormat::Raw => to_bytes(values, width),
+        TableFormat::Ihex => ihex::encode(&[Segment { name: "TABLE".to_string(), address: 0, data: to_bytes(values, width) }]).into_bytes(),
+        TableFormat::Logisim => encode_logisim(values).into_bytes(),
+        TableFormat::Readmemh => values.iter().map(|x| format!("{:0width$x}\n", x, width = width * 2)).collect::<String>().into_bytes(),
+    }
+}
+
+/// Big-endian bytes of every value, cut down to `width` bytes each.
+fn to_bytes(values: &[u64], width: usize) -> Vec<u8> {
+    values.iter().flat_map(|x| x.to_be_bytes()[8 - width..].to_vec()).collect()
+}
+
+/// Logisim images allow run-length encoding ("count*value"), which shrinks the mostly empty tables a lot.
+fn encode_logisim(values: &[u64]) -> String {
+    let mut entries: Vec<String> = Vec::new();
+    let mut i = 0;
+
+    while i < values.len() {
+        let run_length = values[i..].iter().take_while(|&&x| x == values[i]).count();
+
+        if run_length >= 4 {
+            entries.push(format!("{}*{:x}", run_length, values[i]));
+        } else {
+            for _ in 0..run_length { entries.push(format!("{:x}", values[i])); }
+        }
+
+        i += run_length;
+    }
+
+    let mut result = "v2.0 raw\n".to_string();
+
+    for line in entries.chunks(VALUES_PER_LINE) {
+        result += line.join(" ").as_str();
+        result += "\n";
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::output::table::{encode_table, TableFormat};
+
+    #[test]
+    fn test_encode_table() {
+        assert_eq!(encode_table(TableFormat::Raw, &[0x12, 0x34], 1), vec![0x12, 0x34]);
+        assert_eq!(encode_table(TableFormat::Raw, &[0x0102030405060708], 8), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(encode_table(TableFormat::Ihex, &[0x02, 0x33], 1), ":020000000233C9\n:00000001FF\n".as_bytes());
+        assert_eq!(encode_table(TableFormat::Readmemh, &[0x1, 0xAB], 1), "01\nab\n".as_bytes());
+        assert_eq!(encode_table(TableFormat::Readmemh, &[0x1], 8), "0000000000000001\n".as_bytes());
+        assert_eq!(encode_table(TableFormat::Logisim, &[0x1, 0x0, 0x0, 0x0, 0x0, 0x2], 1), "v2.0 raw\n1 4*0 2\n".as_bytes());
+    }
+}
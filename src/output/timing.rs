@@ -0,0 +1,153 @@
+// Timing charts: which control lines an instruction asserts in which stage (for every @ZF/@PM version).
+
+use crate::help::reference::executed_stages;
+use crate::instruction::call_word::flags_description;
+use crate::instruction::instruction::Instruction;
+use crate::instruction::micro_operation::{MicroOperation, MICRO_OPERATIONS};
+
+/// The size of a stage's cell in SVG charts (in px)
+const SVG_CELL_WIDTH: usize = 24;
+const SVG_CELL_HEIGHT: usize = 18;
+
+/// The approximate width of a character in SVG charts (in px)
+const SVG_CHAR_WIDTH: usize = 8;
+
+/// One version of an instruction (e.g. the one for @ZF).
+struct Version {
+    flags: u16,
+    stages: Vec<Vec<String>>,                   // The active micro operations' names by stage
+    lines: Vec<&'static MicroOperation>,        // The micro operations active in any stage
+}
+
+fn versions(instruction: &Instruction) -> Vec<Version> {
+    instruction.flag_versions().iter().map(|&flags| {
+        let stages = executed_stages(instruction, flags);
+        let lines = MICRO_OPERATIONS.iter().filter(|x| stages.iter().any(|y| y.contains(&x.name))).collect();
+
+        Version { flags, stages, lines }
+    }).collect()
+}
+
+fn title(instruction: &Instruction) -> String {
+    let usage = format!("{} {}", instruction.name, instruction.format_notation());
+
+    format!("{} - op-code 0x{:02X}", usage.trim_end(), instruction.op_code)
+}
+
+/// The instructions with the name or the op-code (hex, e.g. 0xA0).
+pub fn find_instructions<'a>(named: &str, instructions: &'a [Instruction]) -> Vec<&'a Instruction> {
+    let op_code = named.strip_prefix("0x").and_then(|x| u16::from_str_radix(x, 16).ok());
+
+    instructions.iter().filter(|x| x.name == named || Some(x.op_code) == op_code).collect()
+}
+
+/// A timing chart for terminals: one row per control line ('#' is asserted), one column per stage.
+pub fn ascii_chart(instruction: &Instruction) -> String {
+    let versions = versions(instruction);
+    let name_width = versions.iter().flat_map(|x| x.lines.iter().map(|y| y.name.len())).max().unwrap_or(0).max(8);
+
+    let mut result = format!("{}\n", title(instruction));
+
+    for version in versions.iter() {
+        let stages = (0..version.stages.len()).map(|x| format!("{:<2}", x)).collect::<String>();
+
+        result.push_str(&format!("\n{:<width$}  {}\n", flags_description(version.flags), stages.trim_end(), width = name_width + 4));
+
+        for line in version.lines.iter() {
+            let cells = version.stages.iter().map(|x| if x.contains(&line.name) { "# " } else { ". " }).collect::<String>();
+            let active_low = if line.is_active_low { "~" } else { " " };
+
+            result.push_str(&format!("{:>2} {}{:<width$}  {}\n", line.pos_in_control_word, active_low, line.name, cells.trim_end(), width = name_width));
+        }
+    }
+
+    result
+}
+
+/// The same chart as `ascii_chart`, but as an SVG image.
+pub fn svg_chart(instruction: &Instruction) -> String {
+    let versions = versions(instruction);
+    let label_width = (versions.iter().flat_map(|x| x.lines.iter().map(|y| y.name.len() + 4)).max().unwrap_or(0).max(12)) * SVG_CHAR_WIDTH;
+    let max_stages = versions.iter().map(|x| x.stages.len()).max().unwrap_or(0);
+
+    let width = label_width + max_stages * SVG_CELL_WIDTH + SVG_CELL_WIDTH;
+    let height = SVG_CELL_HEIGHT * (2 + versions.iter().map(|x| x.lines.len() + 2).sum::<usize>());
+
+    let mut body = format!("<text x=\"4\" y=\"{}\" font-weight=\"bold\">{}</text>\n", SVG_CELL_HEIGHT - 4, escape_xml(&title(instruction)));
+    let mut y = SVG_CELL_HEIGHT * 2;
+
+    for version in versions.iter() {
+        body.push_str(&format!("<text x=\"4\" y=\"{}\" font-style=\"italic\">{}</text>\n", y - 4, escape_xml(&flags_description(version.flags))));
+
+        for stage in 0..version.stages.len() {
+            body.push_str(&format!("<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">{}</text>\n", label_width + stage * SVG_CELL_WIDTH + SVG_CELL_WIDTH / 2, y - 4, stage));
+        }
+
+        for line in version.lines.iter() {
+            let active_low = if line.is_active_low { "~" } else { "" };
+            body.push_str(&format!("<text x=\"4\" y=\"{}\">{} {}{}</text>\n", y + SVG_CELL_HEIGHT - 4, line.pos_in_control_word, active_low, escape_xml(&line.name)));
+
+            for (stage, names) in version.stages.iter().enumerate() {
+                let fill = if names.contains(&line.name) { "#3a7" } else { "#fff" };
+                body.push_str(&format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#999\"/>\n", label_width + stage * SVG_CELL_WIDTH, y, SVG_CELL_WIDTH, SVG_CELL_HEIGHT, fill));
+            }
+
+            y += SVG_CELL_HEIGHT;
+        }
+
+        y += SVG_CELL_HEIGHT * 2;
+    }
+
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n<rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>\n{}</svg>\n", width, height, body)
+}
+
+fn escape_xml(string: &str) -> String {
+    string.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::instruction::Instruction;
+    use crate::output::timing::{ascii_chart, find_instructions, svg_chart};
+
+    fn instruction() -> Instruction {
+        Instruction::from_string("jmpz\nx i\nD2\n@STAGE\nPC_OUT\nMEM_ADDR_PTR_IN\n@STAGE\nRESET_MICRO\n@VERSION\n@ZF\nIMMEDIATE_OUT\nPC_IN\nRESET_MICRO".to_string())
+    }
+
+    #[test]
+    fn test_find_instructions() {
+        let instructions = vec![instruction(), Instruction::new_with_op("add".to_string(), vec![], 0xA0, vec![])];
+
+        assert_eq!(find_instructions("jmpz", &instructions).len(), 1);
+        assert_eq!(find_instructions("0xA0", &instructions)[0].name, "add");
+        assert!(find_instructions("sub", &instructions).is_empty());
+    }
+
+    #[test]
+    fn test_ascii_chart() {
+        let chart = ascii_chart(&instruction());
+        let lines = chart.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines[0], "jmpz x i - op-code 0xD2");
+        assert!(lines[2].starts_with("no flags") && lines[2].ends_with("0 1 2"));
+
+        // No flags: stage 1 fetches, stage 2 resets
+        assert!(chart.lines().any(|x| x.contains("PC_OUT") && x.ends_with(". # .")));
+        assert!(chart.lines().any(|x| x.contains("RESET_MICRO") && x.ends_with(". . #")));
+
+        // The @ZF version jumps in stage 2
+        let zf = chart.split("@ZF").nth(1).unwrap();
+        assert!(zf.lines().any(|x| x.contains("PC_IN") && x.ends_with(". . #")));
+        assert!(!chart.split("@ZF").next().unwrap().contains("PC_IN"));
+    }
+
+    #[test]
+    fn test_svg_chart() {
+        let svg = svg_chart(&instruction());
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(">jmpz x i - op-code 0xD2</text>"));
+        assert_eq!(svg.matches("fill=\"#3a7\"").count(), 8);
+    }
+}
@@ -2,77 +2,57 @@
 // Right now, this hardly does anything special, but highlighting and other stuff can be implemented in the future.
 
 use colorize::AnsiColor;
+use crate::util::diagnostic::Span;
 
 /// Notify the user that a mistake is in the code.
 pub fn display_code_error(kind: ErrorNotificationKind, line: i32, column: Option<u32>, underlined_length: Option<u32>, title: String, message: String, code: Vec<String>, file_name: String) {
-    let formated_title: String;
-    let formated_message: String;
+    let span = Span { file_name, line: line as u32, indent: 0, column, length: underlined_length, code: code[line as usize].clone() };
 
-    match kind {
-        ErrorNotificationKind::Error => {
-            formated_title = format!("Error: {}", title).red().bold().to_string();
-            formated_message = message.red().to_string();
-        }
-
-        ErrorNotificationKind::Warning => {
-            formated_title = format!("Warning: {}", title).yellow().bold().to_string();
-            formated_message = message.yellow().to_string();
-        }
-    }
-
-    // Print the title
-    println!("{}", formated_title);
+    print!("{}", format_code_error(kind, None, title, message, &span));
+}
 
-    let line_number_string = format!("{} ", line);
+/// "Error[E0013]: Title" (or just "Error: Title" without a code), colored by kind.
+pub fn format_title(kind: ErrorNotificationKind, code: Option<&str>, title: &str) -> String {
+    let code = code.map(|x| format!("[{}]", x)).unwrap_or_default();
 
-    // Print the path if it exists
-    if !file_name.is_empty() {
-        for _ in 0..line_number_string.len()-2 /*Guaranteed to be >= 2 (space + numeral)*/{
-            print!(" ");
-        }
-        println!("{}", format!(" --> {}", file_name).blue());
+    match kind {
+        ErrorNotificationKind::Error => format!("Error{}: {}", code, title).red().bold().to_string(),
+        ErrorNotificationKind::Warning => format!("Warning{}: {}", code, title).yellow().bold().to_string(),
     }
+}
 
-    // Print the affected line and the | before and after
+/// The title, the affected line (with the mistake underlined) and the message, ready to be printed.
+pub fn format_code_error(kind: ErrorNotificationKind, code: Option<&str>, title: String, message: String, span: &Span) -> String {
+    let formated_title = format_title(kind.clone(), code, title.as_str());
+    let formated_message = match kind {
+        ErrorNotificationKind::Error => message.red().to_string(),
+        ErrorNotificationKind::Warning => message.yellow().to_string(),
+    };
 
+    // The title
+    let mut result = format!("{}\n", formated_title);
 
-    let mut seperator = String::new();
+    let line_number_string = format!("{} ", span.line);
 
-    for _ in 0..line_number_string.len() {
-        seperator += " ";
+    // The path if it exists
+    if !span.file_name.is_empty() {
+        result += &format_file_name(&line_number_string, &span.file_name);
     }
 
-    seperator += "|";
-    seperator = seperator.blue();
+    // The affected line and the | before and after
+    let seperator = format_seperator(&line_number_string);
 
-    println!("{}", seperator);
-    println!("{}\t{}", format!("{}|", line_number_string).blue(), code[line as usize].clone().to_string());
-    print!("{}", seperator);
-
-    if let Some(column) = column {
-        // Generate an offset in spaces to underline/point to the wrong line
-        let mut offset_string = "\t".to_string();
-
-        for _ in 0..column {
-            offset_string += " ";
-        }
-
-        print!("{}", offset_string);
+    result += &format!("{}\n", seperator);
+    result += &format!("{}\t{}\n", format!("{}|", line_number_string).blue(), span.code);
+    result += &seperator;
 
+    if let Some(column) = span.column {
         // The string that's shown at the position of the mistake.
         let mut hint_string = "^".to_string();
 
         // Look if a length is available, if yes, underline the rest.
-        if let Some(underlined_length) = underlined_length {
-            let mut underline_string = "".to_string();
-
-            if underlined_length > 0 {
-                for _ in 0..underlined_length-1 {
-                    underline_string += "~";
-                }
-            }
-
-            hint_string += underline_string.as_str();
+        if let Some(underlined_length) = span.length {
+            hint_string += "~".repeat(underlined_length.saturating_sub(1) as usize).as_str();
         }
 
         // Make the color appropriate
@@ -81,16 +61,48 @@ pub fn display_code_error(kind: ErrorNotificationKind, line: i32, column: Option
             ErrorNotificationKind::Warning => hint_string = hint_string.yellow().to_string(),
         }
 
-        println!("{}", hint_string);
+        // Offset it in spaces to underline/point to the wrong part of the line
+        result += &format!("\t{}{}\n", " ".repeat(column as usize), hint_string);
+
+        // One more separator to ensure readability as the hint string takes up extra space
+        result += &format!("{}\n", seperator);
+    }
+
+    result += &format!("{}\n\n", formated_message);
+
+    result
+}
+
+/// Another line that belongs to a mistake (e.g. where something has been defined first), underlined and labeled.
+pub fn format_related_line(span: &Span, label: &str) -> String {
+    let line_number_string = format!("{} ", span.line);
+    let seperator = format_seperator(&line_number_string);
 
-        // Print one more separator to ensure readability as the hint string takes up extra space
-        println!("{}", seperator);
+    let mut result = String::new();
+
+    if !span.file_name.is_empty() {
+        result += &format_file_name(&line_number_string, &span.file_name);
     }
 
-    println!("{}\n", formated_message);
+    result += &format!("{}\n", seperator);
+    result += &format!("{}\t{}\n", format!("{}|", line_number_string).blue(), span.code);
+
+    let underline = "-".repeat(span.length.unwrap_or(1).max(1) as usize);
+    result += &format!("{}\t{}{}\n\n", seperator, " ".repeat(span.column.unwrap_or(0) as usize), format!("{} {}", underline, label).blue());
+
+    result
+}
+
+fn format_file_name(line_number_string: &str, file_name: &str) -> String {
+    // Guaranteed to be >= 2 (space + numeral)
+    format!("{}{}\n", " ".repeat(line_number_string.len() - 2), format!(" --> {}", file_name).blue())
+}
+
+fn format_seperator(line_number_string: &str) -> String {
+    format!("{}|", " ".repeat(line_number_string.len())).blue()
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ErrorNotificationKind {
     Error,
     Warning
@@ -0,0 +1,182 @@
+// Errors & warnings found while assembling.
+// They're collected in all steps and rendered at the end (or handed to whoever called the assembler).
+
+use colorize::AnsiColor;
+use crate::util::code_error::{format_code_error, format_related_line, format_title, ErrorNotificationKind};
+
+/// Every diagnostic's stable code (by title). Codes are never reused or renumbered, `smiscasm --explain <code>` explains them.
+pub const DIAGNOSTIC_CODES: [(&str, &str); 30] = [
+    ("E0001", "Library not found"),
+    ("E0002", "Library Download Failed"),
+    ("E0003", "Empty String Literal"),
+    ("E0004", "Unterminated String Literal"),
+    ("E0005", "Compiler command formatting error"),
+    ("E0006", "No Such Mode"),
+    ("E0007", "File Not Found"),
+    ("E0008", "Out Of File Bounds"),
+    ("E0009", "Section Too Long"),
+    ("E0010", "Illegal Character in ASCII"),
+    ("E0011", "Illegal Character in STC"),
+    ("E0012", "Unknown Assembler Instruction"),
+    ("E0013", "Argument Decoding Error"),
+    ("E0014", "Unknown Register"),
+    ("E0015", "No Such Instruction"),
+    ("E0016", "Operand Out Of Range"),
+    ("E0017", "Code Outside Section"),
+    ("W0001", "Unused assembler command"),
+    ("W0002", "Naming Convention Not Met"),
+    ("W0003", "Mode Naming Convention Not Followed"),
+    ("W0004", "Data In Non-Data Mode"),
+    ("W0005", "Code In Inappropriate Section"),
+    ("W0006", "Unused Label"),
+    ("W0007", "Unused Constant"),
+    ("W0008", "Undefined Global"),
+    ("W0009", "Callee-Saved Register Not Restored"),
+    ("W0010", "Reserved Register Written"),
+    ("W0011", "Unreachable Code"),
+    ("W0012", "Fall-Through Into Data"),
+    ("W0013", "Missing Terminator"),
+];
+
+/// The code of diagnostics with that title (if there is one).
+pub fn code_for(title: &str) -> Option<String> {
+    DIAGNOSTIC_CODES.iter().find(|x| x.1 == title).map(|x| x.0.to_string())
+}
+
+/// A place in the code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub file_name: String,          // The file the line is from, empty if it's the input file without a name
+    pub line: u32,                  // The line number in that file
+    pub indent: u32,                // The whitespace characters in the file before code (the column is relative to code)
+    pub column: Option<u32>,        // The start of the faulty part (if known)
+    pub length: Option<u32>,        // The length of the faulty part (if known)
+    pub code: String,               // The line's contents
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: ErrorNotificationKind,
+    pub code: Option<String>,
+    pub title: String,
+    pub message: String,
+    pub primary: Option<Span>,              // Where the mistake is, None if it isn't in a specific line
+    pub secondary: Vec<(Span, String)>,     // Other places that belong to the mistake with a label each
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: ErrorNotificationKind, title: String, message: String, primary: Option<Span>) -> Diagnostic {
+        Diagnostic { severity, code: code_for(&title), title, message, primary, secondary: Vec::new(), notes: Vec::new() }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == ErrorNotificationKind::Error
+    }
+
+    /// The diagnostic as it's shown in the terminal.
+    pub fn render(&self) -> String {
+        let mut result = match &self.primary {
+            Some(span) => format_code_error(self.severity.clone(), self.code.as_deref(), self.title.clone(), self.message.clone(), span),
+            None => {
+                let title = format_title(self.severity.clone(), self.code.as_deref(), self.title.as_str());
+                let message = match self.severity {
+                    ErrorNotificationKind::Error => self.message.clone().red(),
+                    ErrorNotificationKind::Warning => self.message.clone().yellow(),
+                };
+
+                format!("{}\n{}\n\n", title, message)
+            }
+        };
+
+        for (span, label) in self.secondary.iter() {
+            result += &format_related_line(span, label);
+        }
+
+        for note in self.notes.iter() {
+            result += &format!("{}\n", format!("= note: {}", note).blue());
+        }
+
+        if !self.notes.is_empty() { result += "\n"; }
+
+        result
+    }
+}
+
+/// All diagnostics as they're shown in the terminal, followed by a summary.
+pub fn render_diagnostics(diagnostics: &[Diagnostic]) -> String {
+    let errors_count = diagnostics.iter().filter(|x| x.is_error()).count();
+    let warnings_count = diagnostics.len() - errors_count;
+
+    let mut result = diagnostics.iter().map(|x| x.render()).collect::<String>();
+
+    if let Some(code) = diagnostics.iter().find_map(|x| x.code.as_ref()) {
+        result += &format!("{}\n", format!("For more information about a diagnostic, try `smiscasm --explain {}`.", code).blue());
+    }
+
+    if errors_count > 0 {
+        result += &format!("{}\n", format!("Assembling failed with {} errors and {} warnings", errors_count, warnings_count).red().bold());
+    } else if warnings_count > 0 {
+        result += &format!("{}\n", format!("Assembling succeeded with 0 errors and {} warnings", warnings_count).yellow().bold());
+    }
+
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::util::code_error::ErrorNotificationKind;
+    use crate::util::diagnostic::{render_diagnostics, Diagnostic, Span, DIAGNOSTIC_CODES};
+
+    fn span(line: u32, column: u32, length: u32, code: &str) -> Span {
+        Span { file_name: "main.s".to_string(), line, indent: 0, column: Some(column), length: Some(length), code: code.to_string() }
+    }
+
+    #[test]
+    fn test_render() {
+        let mut diagnostic = Diagnostic::new(ErrorNotificationKind::Error, "Label Redefined".to_string(), "main is defined twice.".to_string(), Some(span(7, 0, 4, "main:")));
+        diagnostic.secondary.push((span(2, 0, 4, "main:"), "first defined here".to_string()));
+        diagnostic.notes.push("Rename one of them.".to_string());
+
+        let rendered = diagnostic.render();
+        let lines = rendered.lines().collect::<Vec<&str>>();
+
+        assert!(lines[0].contains("Error: Label Redefined"));
+        assert!(lines[1].contains(" --> main.s"));
+        assert!(lines[3].contains("7 |") && lines[3].ends_with("\tmain:"));
+        assert!(lines[4].contains("^~~~"));
+        assert!(rendered.contains("main is defined twice."));
+        assert!(rendered.contains("---- first defined here"));
+        assert!(rendered.contains("= note: Rename one of them."));
+    }
+
+    #[test]
+    fn test_render_diagnostics() {
+        let warning = Diagnostic::new(ErrorNotificationKind::Warning, "W".to_string(), "".to_string(), None);
+        let error = Diagnostic::new(ErrorNotificationKind::Error, "E".to_string(), "".to_string(), None);
+
+        assert!(render_diagnostics(&[warning.clone(), error]).contains("Assembling failed with 1 errors and 1 warnings"));
+        assert!(render_diagnostics(&[warning]).contains("Assembling succeeded with 0 errors and 1 warnings"));
+        assert_eq!(render_diagnostics(&[]), "");
+
+        let coded = Diagnostic::new(ErrorNotificationKind::Error, "Unknown Register".to_string(), "".to_string(), None);
+        assert!(render_diagnostics(&[coded]).contains("smiscasm --explain E0014"));
+    }
+
+    #[test]
+    fn test_render_code() {
+        let coded = Diagnostic::new(ErrorNotificationKind::Warning, "Naming Convention Not Met".to_string(), "".to_string(), None);
+
+        assert_eq!(coded.code, Some("W0002".to_string()));
+        assert!(coded.render().contains("Warning[W0002]: Naming Convention Not Met"));
+    }
+
+    #[test]
+    fn test_diagnostic_codes() {
+        for (i, (code, title)) in DIAGNOSTIC_CODES.iter().enumerate() {
+            assert!(DIAGNOSTIC_CODES[i + 1..].iter().all(|x| x.0 != *code && x.1 != *title), "{} is used twice", code);
+            assert!(code.len() == 5 && (code.starts_with('E') || code.starts_with('W')));
+        }
+    }
+}
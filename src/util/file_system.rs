@@ -0,0 +1,46 @@
+// Where the assembler reads included files and .incbin data from.
+// The CLI uses the disk, embedders can hand in their files directly.
+
+use std::collections::HashMap;
+use std::fs;
+use crate::util::path::expand_path;
+
+pub trait FileSystem {
+    /// The contents of the file, None if there's no such file (or it can't be read).
+    fn read(&self, path: &str) -> Option<Vec<u8>>;
+
+    fn read_to_string(&self, path: &str) -> Option<String> {
+        String::from_utf8(self.read(path)?).ok()
+    }
+}
+
+/// The real file system (relative to the working directory, ~/ and $PWD/ are expanded).
+pub struct DiskFileSystem;
+
+impl FileSystem for DiskFileSystem {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        fs::read(expand_path(path)?).ok()
+    }
+}
+
+/// Files kept in memory, by path.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileSystem {
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem { files: HashMap::new() }
+    }
+
+    pub fn add(&mut self, path: &str, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.to_string(), contents.into());
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read(&self, path: &str) -> Option<Vec<u8>> {
+        self.files.get(path).cloned()
+    }
+}
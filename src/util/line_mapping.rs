@@ -1,24 +1,25 @@
 // This is a help file for finding the correct line in the original file
 // from another file in a later phase of assembly.
 
-use termimad::crossterm::style::Stylize;
 use crate::config::*;
-use crate::util::code_error::{display_code_error, ErrorNotificationKind};
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::{Diagnostic, Span};
 use crate::util::replacement::Replacement;
 
 #[derive(Debug, Clone)]
 pub struct LineMap{
     pub lines: Vec<LineInfo>,               // The lines that currently exist. Current & Real lines are not one-to-one.
-    pub stop_after_step: bool,              // Stop after the current step has been executed, but finish this one.
+    pub stop_after_step: bool,              // The output can't be worked with: stop after the current step has been executed, but finish this one.
     pub warnings_count: usize,              // The amount of warnings
     pub errors_count: usize,                // The amount of errors. If > 0, compilation will not succeed.
+    pub diagnostics: Vec<Diagnostic>,       // Every error & warning so far (from all steps)
     current_mode: CodeInterpretationMode,   // For adding new lines only, this is a help structure and should never be exposed.
     current_file_name: String,
 }
 
 impl LineMap{
     pub fn new() -> LineMap{
-        LineMap{lines: Vec::new(), stop_after_step: false, warnings_count: 0, errors_count: 0, current_mode: DEFAULT_MODE, current_file_name: ".".to_string() }
+        LineMap{lines: Vec::new(), stop_after_step: false, warnings_count: 0, errors_count: 0, diagnostics: Vec::new(), current_mode: DEFAULT_MODE, current_file_name: ".".to_string() }
     }
 
     #[cfg(test)]
@@ -29,7 +30,7 @@ impl LineMap{
             lines.push(LineInfo::new("as as sas asd".to_string(), 0, vec![(0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1)], i));
         }
 
-        LineMap { lines, stop_after_step: false, warnings_count:0, errors_count:0, current_mode: DEFAULT_MODE, current_file_name: "".to_string() }
+        LineMap { lines, stop_after_step: false, warnings_count:0, errors_count:0, diagnostics: Vec::new(), current_mode: DEFAULT_MODE, current_file_name: "".to_string() }
     }
 
     /// Add a line in the next position
@@ -54,117 +55,65 @@ impl LineMap{
         self.current_mode = mode;
     }
 
-    /// Print an error/warning for a line.
-    pub fn print_notification(&mut self, kind: ErrorNotificationKind, line_number_in_current: u32, token_number: Option<u32>, title: String, message: String) {
-        // Update the counter
-        match kind {
-            ErrorNotificationKind::Warning => {
-                self.warnings_count += 1;
-            },
-            ErrorNotificationKind::Error => {
-                self.errors_count += 1;
-            }
-        }
-
-
-
-        let line_info = self.lines[line_number_in_current as usize].clone();
-        let source_file_name = line_info.source_file_name.clone();
-
-        let mut code: Vec<String> = vec![];
-
-        // Create newlines except for the last (current) line
-
-        for _ in 0..line_info.line_number{
-            code.push(String::new());
-        }
-
-        code.push(line_info.contents);
-
-        if let Some(token_number) = token_number{
-            if let Some(token) = line_info.token_info.iter().nth(token_number as usize){
-                let token = token.clone();
-
-                display_code_error(kind, line_info.line_number as i32, Some(token.0), Some(token.1), title, message, code, source_file_name);
-                return;
-            }
-        }
-
-
-        display_code_error(kind.clone(), line_info.line_number as i32, None, None, title, message + "\n", code, source_file_name);
+    /// Take over the counters & diagnostics of the previous step's map.
+    pub fn carry_over(&mut self, previous: &LineMap){
+        self.warnings_count = previous.warnings_count;
+        self.errors_count = previous.errors_count;
+        self.diagnostics = previous.diagnostics.clone();
     }
 
-
-    /// Output a warning/error with multiple tokens underlined
-    pub fn print_notification_multiple_faulty_tokens(&mut self, kind: ErrorNotificationKind, line_number_in_current: u32, start_token_number: u32, end_token_number: u32, title: String, message: String) {
-        match kind {
-            ErrorNotificationKind::Warning => {
-                self.warnings_count += 1;
-            },
-            ErrorNotificationKind::Error => {
-                self.errors_count += 1;
-            }
+    /// Add an error/warning (and update the counters). Returns it so notes etc. can be attached.
+    pub fn push(&mut self, diagnostic: Diagnostic) -> &mut Diagnostic {
+        match diagnostic.severity {
+            ErrorNotificationKind::Warning => self.warnings_count += 1,
+            ErrorNotificationKind::Error => self.errors_count += 1,
         }
 
+        self.diagnostics.push(diagnostic);
+        self.diagnostics.last_mut().unwrap()
+    }
 
+    /// Where a line (in the current numbering) is in the original code, covering the tokens start...end (if they exist).
+    pub fn span(&self, line_number_in_current: u32, start_token_number: Option<u32>, end_token_number: Option<u32>) -> Span {
+        let line_info = &self.lines[line_number_in_current as usize];
+        let mut span = Span { file_name: line_info.source_file_name.clone(), line: line_info.line_number, indent: line_info.indent, column: None, length: None, code: line_info.contents.clone() };
 
-        let line_info = self.lines[line_number_in_current as usize].clone();
-        let source_file_name = line_info.source_file_name.clone();
-
-        let mut code: Vec<String> = vec![];
-
-        // Create newlines except for the last (current) line
-
-        for _ in 0..line_info.line_number{
-            code.push(String::new());
-        }
+        let start_token = start_token_number.and_then(|x| line_info.token_info.get(x as usize));
+        let end_token = end_token_number.and_then(|x| line_info.token_info.get(x as usize)).or(start_token);
 
-        code.push(line_info.contents);
-        if let Some(start_token) = line_info.token_info.iter().nth(start_token_number as usize){
-            if let Some(end_token) = line_info.token_info.iter().nth(end_token_number as usize){
-                let start_token = start_token.clone();
-                let end_token = end_token.clone();
-                let end_token_end = end_token.0 + end_token.1;
-                let length = end_token_end - start_token.0;
-                // Start the underlined part with the start position of the start token and end it with the stop position of the end token (which is its start pos + its length).
-                display_code_error(kind, line_info.line_number as i32, Some(start_token.0), Some(length), title, message + "\n", code, source_file_name);
-                return;
-            }
+        if let (Some(start_token), Some(end_token)) = (start_token, end_token) {
+            // Start at the start of the start token and end with the end of the end token (which is its start pos + its length).
+            span.column = Some(start_token.0);
+            span.length = Some(end_token.0 + end_token.1 - start_token.0);
         }
 
-
-        display_code_error(kind.clone(), line_info.line_number as i32, None, None, title, message, code, source_file_name);
+        span
     }
 
-    pub fn exit_if_needed(&mut self){
-        if self.stop_after_step{
-            eprintln!("{}", format!("Assembling failed with {} errors and {} warnings", self.errors_count, self.warnings_count).red().to_string());
-            std::process::exit(105);
-        }
+    /// The number of a line's last token (to cover the whole line with report_tokens).
+    pub fn last_token(&self, line_number_in_current: u32) -> u32 {
+        self.lines.get(line_number_in_current as usize).map(|x| x.token_info.len().saturating_sub(1)).unwrap_or(0) as u32
     }
 
-    // Summarize the compilation (show amount of errors & warnings and exit if needed)
-    pub fn summarize(&self){
-        if self.errors_count > 0 {
-            // Create info
-            let info = format!("Assembling failed with {} errors and {} warnings", self.errors_count, self.warnings_count);
+    /// Report an error/warning for a line (or one of its tokens).
+    pub fn report(&mut self, kind: ErrorNotificationKind, line_number_in_current: u32, token_number: Option<u32>, title: String, message: String) -> &mut Diagnostic {
+        let span = self.span(line_number_in_current, token_number, None);
 
-            println!("{}", info.red().bold());
-            std::process::exit(105);
-        }
+        self.push(Diagnostic::new(kind, title, message, Some(span)))
+    }
 
-        if self.warnings_count > 0 {
-            let info = format!("Assembling succeeded with 0 errors and {} warnings", self.warnings_count);
-            // Still some warnings
-            println!("{}", info.yellow().bold());
-        }
+    /// Report an error/warning with multiple tokens underlined
+    pub fn report_tokens(&mut self, kind: ErrorNotificationKind, line_number_in_current: u32, start_token_number: u32, end_token_number: u32, title: String, message: String) -> &mut Diagnostic {
+        let span = self.span(line_number_in_current, Some(start_token_number), Some(end_token_number));
+
+        self.push(Diagnostic::new(kind, title, message, Some(span)))
     }
 }
 
 #[derive(Clone, Debug)]
 pub struct LineInfo {
     pub contents: String,           // The contents (without leading & trailing whitespaces)
-    indent: u32,                    // The indent (in spaces) this line has (for formatting)
+    pub indent: u32,                // The whitespace characters before the contents in the file (token positions start after them)
     pub token_info: Vec<(u32, u32)>,// The start of a token and its length
     pub line_number: u32,           // The original line number
     pub source_file_name: String,
@@ -193,15 +142,7 @@ impl LineInfo{
 
     /// Generate a new LineInfo with text only, without any info about tokens.
     pub fn new_no_info(line: String, line_number: u32) -> Self{
-        let mut line_whitespace_length = 0u32;
-
-        for i in 0..line.len(){
-            match line.clone().chars().nth(i).unwrap() {
-                ' ' => line_whitespace_length += 1,
-                '\t' => line_whitespace_length += 4,
-                _ => break,
-            }
-        }
+        let line_whitespace_length = line.chars().take_while(|x| x.is_whitespace()).count() as u32;
 
         let contents = line.trim().to_string();
 
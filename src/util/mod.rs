@@ -5,3 +5,7 @@ pub mod remove_comments;
 pub mod exit;
 pub mod code_error;
 pub mod line_mapping;
+pub mod path;
+pub mod file_system;
+pub mod diagnostic;
+pub mod warnings;
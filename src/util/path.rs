@@ -0,0 +1,17 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Expands ~/ and $PWD/ at the start of a path.
+pub fn expand_path(path_str: &str) -> Option<PathBuf> {
+    let expanded = if path_str.starts_with("~/") {
+        let home = env::var("HOME").ok()?;
+        PathBuf::from(home).join(&path_str[2..])
+    } else if path_str.starts_with("$PWD/") {
+        let pwd = env::var("PWD").ok()?;
+        PathBuf::from(pwd).join(&path_str[5..])
+    } else {
+        PathBuf::from(path_str)
+    };
+
+    Some(expanded)
+}
@@ -1,7 +1,7 @@
 use std::fmt::Debug;
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Replacement {
+pub struct Replacement {
     initial_value: String,
     new_value: String,
     is_function: bool,
@@ -9,7 +9,7 @@ pub(crate) struct Replacement {
 }
 
 impl Replacement {
-    pub(crate) fn new(_name: String, replacement: String, is_function: bool) -> Replacement {
+    pub fn new(_name: String, replacement: String, is_function: bool) -> Replacement {
         Replacement {initial_value: _name, new_value: replacement, is_function, is_global: false}
     }
 
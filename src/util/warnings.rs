@@ -0,0 +1,142 @@
+// Which warnings are reported and how: -W flags and "# smiscasm: allow(<name>)" comments.
+// Warnings are turned off by their stable names (several codes can share one), errors can't be turned off.
+
+use crate::util::code_error::ErrorNotificationKind;
+use crate::util::diagnostic::Diagnostic;
+
+/// Every warning code's stable name.
+pub const WARNING_NAMES: [(&str, &str); 13] = [
+    ("W0001", "unused-command"),
+    ("W0002", "naming"),
+    ("W0003", "naming"),
+    ("W0004", "data-mode"),
+    ("W0005", "code-section"),
+    ("W0006", "unused"),
+    ("W0007", "unused"),
+    ("W0008", "undefined-global"),
+    ("W0009", "callee-saved"),
+    ("W0010", "reserved-register"),
+    ("W0011", "unreachable"),
+    ("W0012", "fall-through"),
+    ("W0013", "missing-terminator"),
+];
+
+/// What's written in a comment to allow warnings on that line, followed by the names in parentheses.
+const ALLOW_COMMENT: &str = "smiscasm: allow(";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WarningOptions {
+    pub disabled: Vec<String>,      // The names of warnings that aren't reported
+    pub as_errors: bool,            // Report the remaining warnings as errors
+}
+
+impl WarningOptions {
+    /// Read -W flags: "no-<name>" turns a warning off, "error" turns all warnings into errors. Err says what's wrong.
+    pub fn parse(flags: &[String]) -> Result<WarningOptions, String> {
+        let mut result = WarningOptions::default();
+
+        for flag in flags.iter() {
+            if flag == "error" {
+                result.as_errors = true;
+                continue;
+            }
+
+            match flag.strip_prefix("no-") {
+                Some(name) if is_warning_name(name) => result.disabled.push(name.to_string()),
+                _ => return Err(format!("Unknown warning option: -W {}. Use -W error or -W no-<name> with one of: {}.", flag, warning_names().join(", "))),
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+/// All warning names (without duplicates).
+pub fn warning_names() -> Vec<&'static str> {
+    let mut names = WARNING_NAMES.iter().map(|x| x.1).collect::<Vec<&str>>();
+    names.dedup();
+
+    names
+}
+
+fn is_warning_name(name: &str) -> bool {
+    WARNING_NAMES.iter().any(|x| x.1 == name)
+}
+
+/// The stable name of a warning (None for errors and diagnostics without a code).
+pub fn warning_name(diagnostic: &Diagnostic) -> Option<&'static str> {
+    let code = diagnostic.code.as_ref()?;
+
+    WARNING_NAMES.iter().find(|x| x.0 == code).map(|x| x.1)
+}
+
+/// The warning names allowed in a line's comment ("# smiscasm: allow(naming, data-mode)").
+fn allowed_in_line(line: &str) -> Vec<String> {
+    let Some((_, comment)) = line.split_once('#') else { return Vec::new(); };
+    let Some((_, allowed)) = comment.split_once(ALLOW_COMMENT) else { return Vec::new(); };
+    let Some((allowed, _)) = allowed.split_once(')') else { return Vec::new(); };
+
+    allowed.split(',').map(|x| x.trim().to_string()).collect()
+}
+
+/// Remove the warnings that are turned off (by flags or in their line) and turn the rest into errors if asked to.
+pub fn apply_warning_options(diagnostics: Vec<Diagnostic>, options: &WarningOptions) -> Vec<Diagnostic> {
+    diagnostics.into_iter().filter(|diagnostic| {
+        let Some(name) = warning_name(diagnostic) else { return true; };
+        let allowed = diagnostic.primary.as_ref().map(|x| allowed_in_line(&x.code)).unwrap_or_default();
+
+        !options.disabled.iter().any(|x| x == name) && !allowed.iter().any(|x| x == name)
+    }).map(|mut diagnostic| {
+        if options.as_errors && diagnostic.severity == ErrorNotificationKind::Warning {
+            diagnostic.severity = ErrorNotificationKind::Error;
+            diagnostic.notes.push("Warnings are treated as errors (-W error).".to_string());
+        }
+
+        diagnostic
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::util::code_error::ErrorNotificationKind;
+    use crate::util::diagnostic::{Diagnostic, Span};
+    use crate::util::warnings::{apply_warning_options, warning_names, WarningOptions};
+
+    fn diagnostic(title: &str, code: &str) -> Diagnostic {
+        let span = Span { file_name: "main.s".to_string(), line: 1, indent: 0, column: Some(0), length: Some(4), code: code.to_string() };
+
+        Diagnostic::new(ErrorNotificationKind::Warning, title.to_string(), "".to_string(), Some(span))
+    }
+
+    #[test]
+    fn test_parse() {
+        let options = WarningOptions::parse(&["no-naming".to_string(), "error".to_string()]).unwrap();
+
+        assert_eq!(options, WarningOptions { disabled: vec!["naming".to_string()], as_errors: true });
+        assert!(WarningOptions::parse(&["no-nothing".to_string()]).is_err());
+        assert!(WarningOptions::parse(&["naming".to_string()]).is_err());
+        assert_eq!(warning_names(), vec!["unused-command", "naming", "data-mode", "code-section", "unused", "undefined-global", "callee-saved", "reserved-register", "unreachable", "fall-through", "missing-terminator"]);
+    }
+
+    #[test]
+    fn test_apply_warning_options() {
+        let diagnostics = vec![
+            diagnostic("Naming Convention Not Met", "Main:"),
+            diagnostic("Naming Convention Not Met", "Main: # smiscasm: allow(data-mode, naming)"),
+            diagnostic("Data In Non-Data Mode", ".ascii \"a\" # smiscasm: allow(naming)"),
+            Diagnostic::new(ErrorNotificationKind::Error, "Unknown Register".to_string(), "".to_string(), None),
+        ];
+
+        let kept = apply_warning_options(diagnostics.clone(), &WarningOptions::default());
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[1].title, "Data In Non-Data Mode");
+
+        let kept = apply_warning_options(diagnostics.clone(), &WarningOptions { disabled: vec!["naming".to_string()], as_errors: false });
+        assert_eq!(kept.len(), 2);
+
+        let kept = apply_warning_options(diagnostics, &WarningOptions { disabled: Vec::new(), as_errors: true });
+        assert!(kept.iter().all(|x| x.is_error()));
+        assert!(kept[0].notes[0].contains("-W error"));
+    }
+}
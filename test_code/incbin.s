@@ -0,0 +1,12 @@
+.section "CODE"
+main:
+	adrp x0, logo@PAGE
+	add x0, logo@PAGEOFF
+	hlt
+
+.section "DATA"
+.mode data
+logo:
+	.incbin "incbin.bin", 0, 5	# Only "SMISC"
+logo_end:
+	.incbin "incbin.bin", 0x5